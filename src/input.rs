@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::Context;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::model::{
+    Chargeback, ClientId, Deposit, Dispute, Freeze, Resolve, Transaction, TransactionId, Transfer,
+    Unfreeze, Withdrawal,
+};
+
+/// Canonical transaction columns, in the order the deserializer expects them. Only transfers
+/// use `to`, files without transfers may leave it out.
+const COLUMNS: [&str; 5] = ["type", "client", "tx", "amount", TO_COLUMN];
+
+const TO_COLUMN: &str = "to";
+
+/// Position of the `amount` field within a canonical transaction record.
+const AMOUNT_FIELD: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line, see [`json_lines`].
+    JsonLines,
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::JsonLines),
+            other => anyhow::bail!("Unsupported input format `{other}`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InputOptions {
+    pub format: InputFormat,
+    /// Field delimiter, e.g. `;` for files using a comma as decimal separator.
+    pub delimiter: u8,
+    /// Decimal separator used by the `amount` field.
+    pub decimal_separator: char,
+    /// Alternative header names mapped to the canonical column they stand for, e.g.
+    /// `client_id => client`. Canonical names are always recognized.
+    pub column_aliases: HashMap<String, String>,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        Self {
+            format: InputFormat::default(),
+            delimiter: b',',
+            decimal_separator: '.',
+            column_aliases: HashMap::new(),
+        }
+    }
+}
+
+/// Reads transactions from the file at `path` in the format of `options`, like [`read`]. With the
+/// `gzip` feature, files ending in `.gz` are decompressed on the fly. Fails if the file can't be
+/// opened.
+pub fn read_path(
+    path: &Path,
+    options: &InputOptions,
+) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<Transaction>>>> {
+    #[cfg(feature = "gzip")]
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        let file = File::open(path)?;
+
+        return Ok(read(flate2::read::GzDecoder::new(file), options));
+    }
+
+    match options.format {
+        InputFormat::Csv => {
+            let reader = csv_reader(options).from_path(path)?;
+
+            Ok(Box::new(
+                csv_transactions(reader, options).map(|record| record.map_err(Into::into)),
+            ))
+        }
+        InputFormat::JsonLines => Ok(Box::new(json_lines(File::open(path)?))),
+    }
+}
+
+/// Reads transactions in the format of `options`.
+pub fn read<'a, R: io::Read + 'a>(
+    reader: R,
+    options: &InputOptions,
+) -> Box<dyn Iterator<Item = anyhow::Result<Transaction>> + 'a> {
+    match options.format {
+        InputFormat::Csv => {
+            Box::new(transactions(reader, options).map(|record| record.map_err(Into::into)))
+        }
+        InputFormat::JsonLines => Box::new(json_lines(reader)),
+    }
+}
+
+/// Reads transactions from a CSV with a header row. Columns are matched by name (or alias); when
+/// the header doesn't name all canonical columns, fields are read positionally.
+pub fn transactions<R: io::Read>(
+    reader: R,
+    options: &InputOptions,
+) -> impl Iterator<Item = csv::Result<Transaction>> {
+    csv_transactions(csv_reader(options).from_reader(reader), options)
+}
+
+fn csv_reader(options: &InputOptions) -> ReaderBuilder {
+    let mut builder = ReaderBuilder::new();
+    builder.trim(Trim::All).delimiter(options.delimiter);
+    builder
+}
+
+fn csv_transactions<R: io::Read>(
+    mut reader: csv::Reader<R>,
+    options: &InputOptions,
+) -> impl Iterator<Item = csv::Result<Transaction>> {
+    let decimal_separator = options.decimal_separator;
+
+    let (columns, header_error) = match reader.headers() {
+        Ok(headers) => (column_order(headers, &options.column_aliases), None),
+        Err(e) => (None, Some(e)),
+    };
+
+    let records = reader.into_records().map(move |record| {
+        let record = record?;
+
+        if columns.is_none() && decimal_separator == '.' {
+            record.deserialize(None)
+        } else {
+            canonical_record(&record, columns.as_ref(), decimal_separator).deserialize(None)
+        }
+    });
+
+    header_error.map(Err).into_iter().chain(records)
+}
+
+/// Reads transactions from CSV records without a header row, fields in canonical order. The
+/// `amount` field may be left out for disputes, resolves and chargebacks.
+pub fn headerless_transactions<R: io::Read>(
+    reader: R,
+    options: &InputOptions,
+) -> impl Iterator<Item = csv::Result<Transaction>> {
+    let decimal_separator = options.decimal_separator;
+
+    ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .delimiter(options.delimiter)
+        .from_reader(reader)
+        .into_records()
+        .map(move |record| canonical_record(&record?, None, decimal_separator).deserialize(None))
+}
+
+/// Reads transactions from JSON lines, one object per line such as
+/// `{"type":"deposit","client":1,"tx":1,"amount":"1.0"}`. Amounts are strings so they keep their
+/// precision. Blank lines are skipped.
+pub fn json_lines<R: io::Read>(reader: R) -> impl Iterator<Item = anyhow::Result<Transaction>> {
+    io::BufReader::new(reader)
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            if line.trim().is_empty() {
+                return None;
+            }
+
+            let transaction = serde_json::from_str::<JsonTransaction>(&line)
+                .map(Transaction::from)
+                .with_context(|| format!("Invalid transaction on line {}", idx + 1));
+
+            Some(transaction)
+        })
+}
+
+/// A [`Transaction`] as read by [`json_lines`]. The model types only know the positional CSV
+/// layout, hence the separate shape tagged by `type`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonTransaction {
+    Deposit {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(with = "rust_decimal::serde::str")]
+        amount: Decimal,
+    },
+    Withdrawal {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(with = "rust_decimal::serde::str")]
+        amount: Decimal,
+    },
+    Dispute {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    Resolve {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    Chargeback {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    Freeze {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    Unfreeze {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    Transfer {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(with = "rust_decimal::serde::str")]
+        amount: Decimal,
+        to: ClientId,
+    },
+}
+
+impl From<JsonTransaction> for Transaction {
+    fn from(transaction: JsonTransaction) -> Self {
+        match transaction {
+            JsonTransaction::Deposit { client, tx, amount } => Transaction::Deposit(Deposit {
+                client,
+                transaction_id: tx,
+                amount,
+            }),
+            JsonTransaction::Withdrawal { client, tx, amount } => {
+                Transaction::Withdrawal(Withdrawal {
+                    client,
+                    transaction_id: tx,
+                    amount,
+                })
+            }
+            JsonTransaction::Dispute { client, tx } => Transaction::Dispute(Dispute {
+                client,
+                transaction_id: tx,
+            }),
+            JsonTransaction::Resolve { client, tx } => Transaction::Resolve(Resolve {
+                client,
+                transaction_id: tx,
+            }),
+            JsonTransaction::Chargeback { client, tx } => Transaction::Chargeback(Chargeback {
+                client,
+                transaction_id: tx,
+            }),
+            JsonTransaction::Freeze { client, tx } => Transaction::Freeze(Freeze {
+                client,
+                transaction_id: tx,
+            }),
+            JsonTransaction::Unfreeze { client, tx } => Transaction::Unfreeze(Unfreeze {
+                client,
+                transaction_id: tx,
+            }),
+            JsonTransaction::Transfer {
+                client,
+                tx,
+                amount,
+                to,
+            } => Transaction::Transfer(Transfer {
+                from: client,
+                transaction_id: tx,
+                amount,
+                to,
+            }),
+        }
+    }
+}
+
+/// Indices of the canonical columns within `headers`, or `None` if they're already in canonical
+/// order or can't all be found.
+fn column_order(
+    headers: &StringRecord,
+    aliases: &HashMap<String, String>,
+) -> Option<[usize; COLUMNS.len()]> {
+    let names: Vec<&str> = headers
+        .iter()
+        .map(|name| aliases.get(name).map_or(name, String::as_str))
+        .collect();
+
+    let mut order = [0; COLUMNS.len()];
+
+    for (idx, column) in order.iter_mut().zip(COLUMNS) {
+        *idx = match names.iter().position(|name| *name == column) {
+            Some(position) => position,
+            // read as an empty field
+            None if column == TO_COLUMN => usize::MAX,
+            None => return None,
+        };
+    }
+
+    Some(order).filter(|order| !matches!(order, [0, 1, 2, 3, 4 | usize::MAX]))
+}
+
+fn canonical_record(
+    record: &StringRecord,
+    columns: Option<&[usize; COLUMNS.len()]>,
+    decimal_separator: char,
+) -> StringRecord {
+    let fields: Vec<&str> = match columns {
+        Some(columns) => columns
+            .iter()
+            .map(|&idx| record.get(idx).unwrap_or_default())
+            .collect(),
+        None => record.iter().collect(),
+    };
+
+    let mut canonical: StringRecord = fields
+        .into_iter()
+        .enumerate()
+        .map(|(idx, field)| match idx {
+            AMOUNT_FIELD => field.replace(decimal_separator, "."),
+            _ => field.to_owned(),
+        })
+        .collect();
+
+    canonical.set_position(record.position().cloned());
+    canonical
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_boxed_reader_is_trimmed() {
+        let csv = indoc::indoc! {"
+            type , client , tx , amount
+             deposit ,  1 , 1 ,  1.5
+        "};
+
+        let reader: Box<dyn io::Read> = Box::new(csv.as_bytes());
+
+        let transactions: Vec<_> = transactions(reader, &InputOptions::default())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            transactions,
+            [Transaction::Deposit(Deposit {
+                client: 1.into(),
+                transaction_id: 1.into(),
+                amount: Decimal::new(15, 1),
+            })]
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_round_trip() {
+        use std::io::Write;
+
+        let csv = indoc::indoc! {"
+            type, client, tx, amount
+            deposit, 1, 1, 1.5
+            deposit, 2, 2, 2.0
+            withdrawal, 1, 3, 0.5
+            dispute, 2, 2,
+        "};
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(csv.as_bytes()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("tp-input-{}.csv.gz", std::process::id()));
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let processor = crate::process::process_path(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            summary_csv(processor.unwrap()),
+            "client,available,held,total,locked\n1,1.0,0.0,1.0,false\n2,0.0,2.0,2.0,false\n"
+        );
+    }
+
+    #[test]
+    fn test_read_path() {
+        let csv = indoc::indoc! {"
+            type, client, tx, amount
+            deposit, 1, 1, 1.5
+            withdrawal, 1, 2, 0.5
+        "};
+
+        let path = std::env::temp_dir().join(format!("tp-input-{}.csv", std::process::id()));
+        std::fs::write(&path, csv).unwrap();
+
+        let processor = crate::process::process_path(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            summary_csv(processor.unwrap()),
+            "client,available,held,total,locked\n1,1.0,0.0,1.0,false\n"
+        );
+    }
+
+    fn summary_csv(processor: crate::processor::TransactionProcessor) -> String {
+        let mut output = Vec::new();
+
+        crate::output::write_summaries(
+            &mut output,
+            &crate::output::OutputOptions::default(),
+            processor.summary(),
+        )
+        .unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let jsonl = indoc::indoc! {r#"
+            {"type":"deposit","client":1,"tx":1,"amount":"1.5"}
+            {"type":"withdrawal","client":1,"tx":2,"amount":"0.25"}
+
+            {"type":"dispute","client":1,"tx":1}
+            {"type":"resolve","client":1,"tx":1,"amount":null}
+            {"type":"chargeback","client":1,"tx":2}
+        "#};
+
+        let parsed: Vec<_> = json_lines(jsonl.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            parsed,
+            [
+                Transaction::Deposit(Deposit {
+                    client: 1.into(),
+                    transaction_id: 1.into(),
+                    amount: Decimal::new(15, 1),
+                }),
+                Transaction::Withdrawal(Withdrawal {
+                    client: 1.into(),
+                    transaction_id: 2.into(),
+                    amount: Decimal::new(25, 2),
+                }),
+                Transaction::Dispute(Dispute {
+                    client: 1.into(),
+                    transaction_id: 1.into(),
+                }),
+                Transaction::Resolve(Resolve {
+                    client: 1.into(),
+                    transaction_id: 1.into(),
+                }),
+                Transaction::Chargeback(Chargeback {
+                    client: 1.into(),
+                    transaction_id: 2.into(),
+                }),
+            ]
+        );
+
+        let error = json_lines(r#"{"type":"refund","client":1,"tx":1}"#.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "Invalid transaction on line 1");
+    }
+
+    #[test]
+    fn test_missing_ids_are_reported() {
+        let csv = indoc::indoc! {"
+            type, client, tx, amount
+            deposit, , 1, 1.0
+            deposit, 1, , 1.0
+        "};
+
+        let errors: Vec<_> = transactions(csv.as_bytes(), &InputOptions::default())
+            .map(|tx| tx.unwrap_err().to_string())
+            .collect();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("line: 2"), "{}", errors[0]);
+        assert!(errors[0].contains("missing client id"), "{}", errors[0]);
+        assert!(errors[1].contains("line: 3"), "{}", errors[1]);
+        assert!(errors[1].contains("missing tx id"), "{}", errors[1]);
+    }
+
+    #[test]
+    fn test_comma_decimal_separator() {
+        let csv = indoc::indoc! {"
+            type; client; tx; amount
+            deposit; 1; 1; 1,50
+            withdrawal; 1; 2; 0,25
+            dispute; 1; 1;
+        "};
+
+        let options = InputOptions {
+            delimiter: b';',
+            decimal_separator: ',',
+            ..Default::default()
+        };
+
+        let parsed: Vec<_> = transactions(csv.as_bytes(), &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            parsed,
+            [
+                Transaction::Deposit(Deposit {
+                    client: 1.into(),
+                    transaction_id: 1.into(),
+                    amount: Decimal::new(150, 2),
+                }),
+                Transaction::Withdrawal(Withdrawal {
+                    client: 1.into(),
+                    transaction_id: 2.into(),
+                    amount: Decimal::new(25, 2),
+                }),
+                Transaction::Dispute(Dispute {
+                    client: 1.into(),
+                    transaction_id: 1.into(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transfer_recipient_column() {
+        let csv = indoc::indoc! {"
+            to, type, client, tx, amount
+            , deposit, 1, 1, 2.5
+            2, transfer, 1, 2, 1.0
+        "};
+
+        let parsed: Vec<_> = transactions(csv.as_bytes(), &InputOptions::default())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            parsed[1],
+            Transaction::Transfer(Transfer {
+                from: 1.into(),
+                transaction_id: 2.into(),
+                amount: Decimal::new(10, 1),
+                to: 2.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_column_aliases() {
+        let csv = indoc::indoc! {"
+            client_id, transaction, amount, tx
+            1, deposit, 2.5, 1
+            1, dispute, , 1
+        "};
+
+        let options = InputOptions {
+            column_aliases: HashMap::from([
+                ("client_id".to_owned(), "client".to_owned()),
+                ("transaction".to_owned(), "type".to_owned()),
+            ]),
+            ..Default::default()
+        };
+
+        let parsed: Vec<_> = transactions(csv.as_bytes(), &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            parsed,
+            [
+                Transaction::Deposit(Deposit {
+                    client: 1.into(),
+                    transaction_id: 1.into(),
+                    amount: Decimal::new(25, 1),
+                }),
+                Transaction::Dispute(Dispute {
+                    client: 1.into(),
+                    transaction_id: 1.into(),
+                }),
+            ]
+        );
+    }
+}
@@ -1,2 +1,7 @@
+pub mod input;
 pub mod model;
+pub mod output;
+pub mod process;
 pub mod processor;
+#[cfg(feature = "statsd")]
+pub mod statsd;
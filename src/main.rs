@@ -1,39 +1,446 @@
+use std::fs::File;
 use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::Context;
-use csv::{ReaderBuilder, Trim, WriterBuilder};
+use serde::Serialize;
 
+use tp::input::{self, InputOptions};
 use tp::model::Transaction;
-use tp::processor::TransactionProcessor;
+use tp::output::{self, HashingWriter, OutputOptions, SummaryRounding};
+use tp::process;
+use tp::processor::{self, ProcessorConfig, TransactionProcessor};
+
+struct Args {
+    /// Processed left to right as a single stream, stdin when empty.
+    filenames: Vec<String>,
+    input: InputOptions,
+    output: OutputOptions,
+    /// Where the summary goes, stdout when `None`.
+    output_path: Option<PathBuf>,
+    summary_only: bool,
+    /// Number of summary rows written, lowest client ids first.
+    limit: Option<usize>,
+    timestamp: bool,
+    /// Add a `version` column with the version of the engine.
+    version_column: bool,
+    rounding: SummaryRounding,
+    rejects: Option<PathBuf>,
+    type_priority: bool,
+    reverse: bool,
+    clamp_negative: Option<PathBuf>,
+    normalize: bool,
+    transaction_counts: bool,
+    include_errors: bool,
+    snapshot_in: Option<PathBuf>,
+    snapshot_out: Option<PathBuf>,
+    manifest: Option<PathBuf>,
+    repl: bool,
+    #[cfg(feature = "statsd")]
+    statsd: Option<String>,
+}
+
+impl Args {
+    fn parse() -> anyhow::Result<Self> {
+        let mut filenames = Vec::new();
+        let mut input = InputOptions::default();
+        let mut output = OutputOptions::default();
+        let mut output_path = None;
+        let mut summary_only = false;
+        let mut limit = None;
+        let mut timestamp = false;
+        let mut version_column = false;
+        let mut rounding = SummaryRounding::default();
+        let mut rejects = None;
+        let mut type_priority = false;
+        let mut reverse = false;
+        let mut clamp_negative = None;
+        let mut normalize = false;
+        let mut transaction_counts = false;
+        let mut include_errors = false;
+        let mut snapshot_in = None;
+        let mut snapshot_out = None;
+        let mut manifest = None;
+        let mut repl = false;
+        #[cfg(feature = "statsd")]
+        let mut statsd = None;
+
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    output.format = args
+                        .next()
+                        .context("Missing value for `--format`")?
+                        .parse()?;
+                }
+                "--input-format" => input.format = value(&mut args, &arg)?,
+                "--delimiter" => {
+                    let delimiter = single_char(args.next(), "--delimiter")?;
+
+                    input.delimiter = u8::try_from(delimiter)
+                        .ok()
+                        .filter(u8::is_ascii)
+                        .context("`--delimiter` must be an ASCII character")?;
+                }
+                "--decimal-separator" => {
+                    input.decimal_separator = single_char(args.next(), "--decimal-separator")?;
+                }
+                "--column-alias" => {
+                    let alias = args.next().context("Missing value for `--column-alias`")?;
+                    let (alias, column) = alias
+                        .split_once('=')
+                        .context("`--column-alias` expects `alias=column`")?;
+
+                    input
+                        .column_aliases
+                        .insert(alias.to_owned(), column.to_owned());
+                }
+                "-o" | "--output" => {
+                    output_path = Some(
+                        args.next()
+                            .with_context(|| format!("Missing value for `{arg}`"))?
+                            .into(),
+                    );
+                }
+                "--no-headers" => output.headers = false,
+                "--numeric-locked" => output.numeric_locked = true,
+                "--column-widths" => output.column_widths = value(&mut args, &arg)?,
+                "--summary-only" => summary_only = true,
+                "--limit" => {
+                    limit = Some(
+                        args.next()
+                            .context("Missing value for `--limit`")?
+                            .parse()
+                            .context("`--limit` expects a number of rows")?,
+                    );
+                }
+                "--timestamp" => timestamp = true,
+                "--version-column" => version_column = true,
+                "--round-available" => rounding.available = Some(value(&mut args, &arg)?),
+                "--round-held" => rounding.held = Some(value(&mut args, &arg)?),
+                "--round-total" => rounding.total = Some(value(&mut args, &arg)?),
+                "--rejects" => {
+                    rejects = Some(args.next().context("Missing value for `--rejects`")?.into());
+                }
+                "--type-priority" => type_priority = true,
+                "--reverse" => reverse = true,
+                "--normalize" => normalize = true,
+                "--transaction-counts" => transaction_counts = true,
+                "--include-errors" => include_errors = true,
+                "--snapshot-in" => {
+                    snapshot_in = Some(
+                        args.next()
+                            .context("Missing value for `--snapshot-in`")?
+                            .into(),
+                    );
+                }
+                "--snapshot-out" => {
+                    snapshot_out = Some(
+                        args.next()
+                            .context("Missing value for `--snapshot-out`")?
+                            .into(),
+                    );
+                }
+                "--manifest" => {
+                    manifest = Some(
+                        args.next()
+                            .context("Missing value for `--manifest`")?
+                            .into(),
+                    );
+                }
+                "--clamp-negative" => {
+                    clamp_negative = Some(
+                        args.next()
+                            .context("Missing value for `--clamp-negative`")?
+                            .into(),
+                    );
+                }
+                "--repl" => repl = true,
+                #[cfg(feature = "statsd")]
+                "--statsd" => statsd = Some(args.next().context("Missing value for `--statsd`")?),
+                _ => filenames.push(arg),
+            }
+        }
+
+        if normalize && rounding != SummaryRounding::default() {
+            anyhow::bail!("`--normalize` cannot be combined with `--round-*`");
+        }
+
+        Ok(Self {
+            filenames,
+            input,
+            output,
+            output_path,
+            summary_only,
+            limit,
+            timestamp,
+            version_column,
+            rounding,
+            rejects,
+            type_priority,
+            reverse,
+            clamp_negative,
+            normalize,
+            transaction_counts,
+            include_errors,
+            snapshot_in,
+            snapshot_out,
+            manifest,
+            repl,
+            #[cfg(feature = "statsd")]
+            statsd,
+        })
+    }
+}
+
+/// Self-describing record of a batch run, written with `--manifest`.
+#[derive(Serialize)]
+struct Manifest<'a> {
+    /// Empty when reading stdin.
+    inputs: &'a [String],
+    records: usize,
+    accounts: usize,
+    locked_accounts: usize,
+    errors: usize,
+    config: ProcessorConfig,
+    /// 64-bit FNV-1a hash of the summary output, as hex.
+    output_hash: String,
+}
+
+fn value<T>(args: &mut impl Iterator<Item = String>, flag: &str) -> anyhow::Result<T>
+where
+    T: FromStr<Err = anyhow::Error>,
+{
+    args.next()
+        .with_context(|| format!("Missing value for `{flag}`"))?
+        .parse()
+}
+
+fn single_char(value: Option<String>, flag: &str) -> anyhow::Result<char> {
+    let value = value.with_context(|| format!("Missing value for `{flag}`"))?;
+    let mut chars = value.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => anyhow::bail!("`{flag}` expects a single character, got `{value}`"),
+    }
+}
 
 fn main() -> anyhow::Result<()> {
-    let filename = std::env::args()
-        .nth(1)
-        .context("Missing path to csv file.\nTry running `cargo run -- filename.csv`")?;
+    let args = Args::parse()?;
+
+    let mut handler = TransactionProcessor::default()
+        .with_rejected_retention(args.rejects.is_some())
+        .with_normalized_amounts(args.normalize)
+        .with_transaction_counts_report(args.transaction_counts)
+        .with_errors_in_summary(args.include_errors);
+
+    if let Some(path) = &args.snapshot_in {
+        let file =
+            File::open(path).with_context(|| format!("Failed opening `{}`", path.display()))?;
+        let snapshot = serde_json::from_reader(io::BufReader::new(file))
+            .with_context(|| format!("Failed reading snapshot `{}`", path.display()))?;
+
+        handler = handler.with_snapshot(snapshot);
+    }
+
+    if args.repl {
+        repl(&mut handler, &args)?;
+
+        return save_snapshot(&handler, &args);
+    }
+
+    let records = read_inputs(&args.filenames, &args.input)?
+        .into_iter()
+        .flatten();
+
+    let record_count = if args.type_priority || args.reverse {
+        let mut batch = records
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed parsing file")?;
+
+        if args.reverse {
+            batch.reverse();
+        }
+
+        // the sort is stable, so a reversed batch stays reversed within each type
+        if args.type_priority {
+            processor::sort_by_type_priority(&mut batch);
+        }
+
+        process::process_records(&mut handler, batch.into_iter().map(Ok))?
+    } else {
+        process::process_records(&mut handler, records)?
+    };
+
+    save_snapshot(&handler, &args)?;
+
+    #[cfg(feature = "statsd")]
+    if let Some(addr) = &args.statsd {
+        tp::statsd::emit(addr.as_str(), "tp", &handler.stats())
+            .with_context(|| format!("Failed sending metrics to `{addr}`"))?;
+    }
 
-    let mut reader = ReaderBuilder::new()
-        .trim(Trim::All)
-        .from_path(filename)
-        .unwrap();
+    if let Some(path) = &args.rejects {
+        let file =
+            File::create(path).with_context(|| format!("Failed creating `{}`", path.display()))?;
 
-    let mut handler = TransactionProcessor::default();
+        output::write_transactions(file, &args.input, handler.take_rejected())?;
+    }
+
+    for error in handler.errors() {
+        eprintln!("{error}");
+    }
+
+    let had_errors = handler.had_errors();
+    let stats = handler.stats();
+    let config = handler.config().clone();
+    let mut writer = HashingWriter::new(open_output(args.output_path.as_deref())?);
+
+    if args.summary_only {
+        output::write_grand_totals(&mut writer, &args.output, handler.grand_totals())?;
+    } else {
+        let mut clamped = Vec::new();
+        let processed_at = args
+            .timestamp
+            .then(|| SystemTime::now().duration_since(UNIX_EPOCH))
+            .transpose()?
+            .map(|elapsed| elapsed.as_secs());
+        let version = args
+            .version_column
+            .then(|| env!("CARGO_PKG_VERSION").to_owned());
+
+        let summaries = handler
+            .summary()
+            .take(args.limit.unwrap_or(usize::MAX))
+            .map(|mut summary| {
+                if args.clamp_negative.is_some() {
+                    clamped.extend(output::clamp_negative(&mut summary));
+                }
+
+                args.rounding.apply(&mut summary);
+                summary.processed_at = processed_at;
+                summary.version.clone_from(&version);
+                summary
+            });
+
+        output::write_summaries(&mut writer, &args.output, summaries)?;
+
+        if let Some(path) = &args.clamp_negative {
+            let file = File::create(path)
+                .with_context(|| format!("Failed creating `{}`", path.display()))?;
+
+            output::write_clamped(file, &OutputOptions::default(), clamped)?;
+        }
+    }
+
+    if let Some(path) = &args.manifest {
+        let manifest = Manifest {
+            inputs: &args.filenames,
+            records: record_count,
+            accounts: stats.accounts,
+            locked_accounts: stats.locked_accounts,
+            errors: stats.rejected,
+            config,
+            output_hash: format!("{:016x}", writer.hash()),
+        };
+
+        let file =
+            File::create(path).with_context(|| format!("Failed creating `{}`", path.display()))?;
+
+        serde_json::to_writer_pretty(file, &manifest)
+            .with_context(|| format!("Failed writing manifest `{}`", path.display()))?;
+    }
+
+    // the writers flush on completion, so exiting doesn't lose any output
+    if had_errors {
+        std::process::exit(1);
+    }
 
-    for record in reader.deserialize() {
-        let transaction: Transaction = record.context("Failed parsing file")?;
+    Ok(())
+}
+
+/// Handles transactions read line by line from stdin, printing the summary of the affected account
+/// after each one. Errors are reported on stderr.
+fn repl(handler: &mut TransactionProcessor, args: &Args) -> anyhow::Result<()> {
+    let options = OutputOptions {
+        headers: false,
+        ..args.output
+    };
+
+    for record in input::headerless_transactions(io::stdin().lock(), &args.input) {
+        let transaction = match record {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                eprintln!("Failed parsing transaction: {e}");
+                continue;
+            }
+        };
+
+        let client = transaction.client_id();
 
-        handler.handle(transaction);
+        if let Err(error) = handler.handle(transaction) {
+            eprintln!("{error}");
+        }
+
+        // the error was reported above, keep the log from growing over a long session
+        handler.take_errors();
+
+        output::write_summaries(
+            io::stdout().lock(),
+            &options,
+            handler.summary_for_clients(&[client]),
+        )?;
     }
 
-    let stdout = io::stdout().lock();
-    let mut writer = WriterBuilder::new().from_writer(stdout);
+    Ok(())
+}
 
-    for record in handler.summary() {
-        writer
-            .serialize(record)
-            .context("Failed producing output")?;
+/// The transactions of the files at `filenames`, in order, or of stdin when there are none so data
+/// can be piped in. Every file is opened up front.
+fn read_inputs(
+    filenames: &[String],
+    options: &InputOptions,
+) -> anyhow::Result<Vec<Box<dyn Iterator<Item = anyhow::Result<Transaction>>>>> {
+    if filenames.is_empty() {
+        return Ok(vec![input::read(io::stdin().lock(), options)]);
     }
 
-    writer.flush().unwrap();
+    filenames
+        .iter()
+        .map(|filename| {
+            input::read_path(Path::new(filename), options)
+                .with_context(|| format!("Failed opening `{filename}`"))
+        })
+        .collect()
+}
+
+/// The file at `path`, or stdout when there is none. Output is flushed by the writers in
+/// [`output`].
+fn open_output(path: Option<&Path>) -> anyhow::Result<Box<dyn io::Write>> {
+    match path {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed creating `{}`", path.display()))?;
+
+            Ok(Box::new(file))
+        }
+        None => Ok(Box::new(io::stdout().lock())),
+    }
+}
+
+fn save_snapshot(handler: &TransactionProcessor, args: &Args) -> anyhow::Result<()> {
+    if let Some(path) = &args.snapshot_out {
+        let file =
+            File::create(path).with_context(|| format!("Failed creating `{}`", path.display()))?;
+
+        serde_json::to_writer_pretty(file, &handler.snapshot())
+            .with_context(|| format!("Failed writing snapshot `{}`", path.display()))?;
+    }
 
     Ok(())
 }
@@ -3,11 +3,14 @@ mod transaction;
 
 use std::fmt::Display;
 
-pub use account::{Account, AccountSummary};
-use serde::{Deserialize, Serialize};
-pub use transaction::{Chargeback, Deposit, Dispute, Resolve, Transaction, Withdrawal};
-
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub use account::{Account, AccountMemo, AccountSummary, GrandTotals};
+use serde::{de, Deserialize, Deserializer, Serialize};
+pub use transaction::{
+    Chargeback, Deposit, Dispute, Freeze, Resolve, Transaction, TransactionType, Transfer,
+    Unfreeze, Withdrawal,
+};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 #[repr(transparent)]
 pub struct ClientId(u16);
 
@@ -17,13 +20,22 @@ impl From<u16> for ClientId {
     }
 }
 
+// an empty field would otherwise surface as an opaque integer parse error
+impl<'de> Deserialize<'de> for ClientId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Option::deserialize(deserializer)?
+            .map(Self)
+            .ok_or_else(|| de::Error::custom("missing client id"))
+    }
+}
+
 impl Display for ClientId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 #[repr(transparent)]
 pub struct TransactionId(u32);
 
@@ -33,6 +45,14 @@ impl From<u32> for TransactionId {
     }
 }
 
+impl<'de> Deserialize<'de> for TransactionId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Option::deserialize(deserializer)?
+            .map(Self)
+            .ok_or_else(|| de::Error::custom("missing tx id"))
+    }
+}
+
 impl Display for TransactionId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
@@ -1,5 +1,5 @@
 use rust_decimal::Decimal;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::processor::ProcessingError;
 
@@ -10,14 +10,103 @@ pub struct Account {
     pub available: Decimal,
     pub held: Decimal,
     pub locked: bool,
-    pub error: Option<ProcessingError>,
+    /// Every rejection of the account's transactions, in order.
+    pub errors: Vec<ProcessingError>,
+    pub open_deposit_disputes: usize,
+    pub open_withdrawal_disputes: usize,
+    pub transaction_count: usize,
+    /// Number of deposits credited to the account.
+    pub deposit_count: usize,
+    /// Number of withdrawals debited from the account.
+    pub withdrawal_count: usize,
+    /// Sum of all deposits credited to the account.
+    pub total_deposited: Decimal,
+    /// Position in the processed stream of the transaction that opened the account, `None` for
+    /// accounts carried over from before processing started.
+    pub opened_at: Option<usize>,
 }
 
-#[derive(Debug, Serialize)]
+impl Account {
+    pub(crate) fn close_dispute(&mut self, is_deposit: bool) {
+        if is_deposit {
+            self.open_deposit_disputes -= 1;
+        } else {
+            self.open_withdrawal_disputes -= 1;
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
 pub struct AccountSummary {
     pub client: ClientId,
     pub available: Decimal,
     pub held: Decimal,
     pub total: Decimal,
     pub locked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_deposit_disputes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_withdrawal_disputes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<AccountMemo>,
+    /// Unix time in seconds at which processing completed, the same for every row of a run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processed_at: Option<u64>,
+    /// Version of the engine that produced the summary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deposit_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub withdrawal_count: Option<usize>,
+    /// The first error of the account, empty for accounts without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set when `available + held` overflows; `total` then holds the saturated value.
+    #[serde(skip)]
+    pub anomalous: bool,
+}
+
+impl AccountSummary {
+    /// Spendable funds, i.e. the balance excluding anything held in dispute.
+    pub fn net_available(&self) -> Decimal {
+        self.available
+    }
+}
+
+/// Status derived from an account's state, for triaging summaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AccountMemo {
+    Ok,
+    Locked,
+    HasOpenDisputes,
+    Anomalous,
+}
+
+impl AccountMemo {
+    pub fn of(account: &Account) -> Self {
+        if account.locked {
+            AccountMemo::Locked
+        } else if account.open_deposit_disputes + account.open_withdrawal_disputes > 0 {
+            AccountMemo::HasOpenDisputes
+        } else {
+            AccountMemo::Ok
+        }
+    }
+}
+
+/// Aggregate over all reported accounts.
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct GrandTotals {
+    pub clients: usize,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked_accounts: usize,
+    /// Set when one of the sums overflows; it then holds the saturated value.
+    #[serde(skip)]
+    pub anomalous: bool,
 }
@@ -1,10 +1,24 @@
 use rust_decimal::Decimal;
 use serde::de::{self, Error, Visitor};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::{ClientId, TransactionId};
 
-#[derive(Debug)]
+/// The type of a [`Transaction`], without its data.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionType {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Freeze,
+    Unfreeze,
+    Transfer,
+}
+
+#[derive(Debug, Clone)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum Transaction {
     Deposit(Deposit),
@@ -12,9 +26,12 @@ pub enum Transaction {
     Dispute(Dispute),
     Resolve(Resolve),
     Chargeback(Chargeback),
+    Freeze(Freeze),
+    Unfreeze(Unfreeze),
+    Transfer(Transfer),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Deposit {
     pub client: ClientId,
@@ -23,7 +40,7 @@ pub struct Deposit {
     pub amount: Decimal,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Withdrawal {
     pub client: ClientId,
@@ -32,7 +49,7 @@ pub struct Withdrawal {
     pub amount: Decimal,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Dispute {
     pub client: ClientId,
@@ -40,7 +57,7 @@ pub struct Dispute {
     pub transaction_id: TransactionId,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Resolve {
     pub client: ClientId,
@@ -48,15 +65,46 @@ pub struct Resolve {
     pub transaction_id: TransactionId,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
-
 pub struct Chargeback {
     pub client: ClientId,
     #[serde(rename = "tx")]
     pub transaction_id: TransactionId,
 }
 
+/// Administrative lock of an account, e.g. on request of compliance. The account is treated as
+/// if charged back until an [`Unfreeze`]. `transaction_id` only identifies the request.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Freeze {
+    pub client: ClientId,
+    #[serde(rename = "tx")]
+    pub transaction_id: TransactionId,
+}
+
+/// Lifts the lock of an account, whether set by a [`Freeze`] or a chargeback.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Unfreeze {
+    pub client: ClientId,
+    #[serde(rename = "tx")]
+    pub transaction_id: TransactionId,
+}
+
+/// Moves funds from the `available` balance of one client to another's. Transfers are final,
+/// they can't be disputed. The receiver is read from the `to` column, after `amount`.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Transfer {
+    #[serde(rename = "client")]
+    pub from: ClientId,
+    #[serde(rename = "tx")]
+    pub transaction_id: TransactionId,
+    pub amount: Decimal,
+    pub to: ClientId,
+}
+
 impl Transaction {
     pub fn client_id(&self) -> ClientId {
         match self {
@@ -65,6 +113,9 @@ impl Transaction {
             Transaction::Dispute(t) => t.client,
             Transaction::Resolve(t) => t.client,
             Transaction::Chargeback(t) => t.client,
+            Transaction::Freeze(t) => t.client,
+            Transaction::Unfreeze(t) => t.client,
+            Transaction::Transfer(t) => t.from,
         }
     }
 
@@ -75,6 +126,49 @@ impl Transaction {
             Transaction::Dispute(t) => t.transaction_id,
             Transaction::Resolve(t) => t.transaction_id,
             Transaction::Chargeback(t) => t.transaction_id,
+            Transaction::Freeze(t) => t.transaction_id,
+            Transaction::Unfreeze(t) => t.transaction_id,
+            Transaction::Transfer(t) => t.transaction_id,
+        }
+    }
+
+    pub fn transaction_type(&self) -> TransactionType {
+        match self {
+            Transaction::Deposit(_) => TransactionType::Deposit,
+            Transaction::Withdrawal(_) => TransactionType::Withdrawal,
+            Transaction::Dispute(_) => TransactionType::Dispute,
+            Transaction::Resolve(_) => TransactionType::Resolve,
+            Transaction::Chargeback(_) => TransactionType::Chargeback,
+            Transaction::Freeze(_) => TransactionType::Freeze,
+            Transaction::Unfreeze(_) => TransactionType::Unfreeze,
+            Transaction::Transfer(_) => TransactionType::Transfer,
+        }
+    }
+
+    /// The tag identifying the transaction type in the input format.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Transaction::Deposit(_) => "deposit",
+            Transaction::Withdrawal(_) => "withdrawal",
+            Transaction::Dispute(_) => "dispute",
+            Transaction::Resolve(_) => "resolve",
+            Transaction::Chargeback(_) => "chargeback",
+            Transaction::Freeze(_) => "freeze",
+            Transaction::Unfreeze(_) => "unfreeze",
+            Transaction::Transfer(_) => "transfer",
+        }
+    }
+
+    pub fn amount(&self) -> Option<Decimal> {
+        match self {
+            Transaction::Deposit(t) => Some(t.amount),
+            Transaction::Withdrawal(t) => Some(t.amount),
+            Transaction::Transfer(t) => Some(t.amount),
+            Transaction::Dispute(_)
+            | Transaction::Resolve(_)
+            | Transaction::Chargeback(_)
+            | Transaction::Freeze(_)
+            | Transaction::Unfreeze(_) => None,
         }
     }
 }
@@ -113,9 +207,21 @@ impl<'de> Visitor<'de> for TransactionVisitor {
             "dispute" => Dispute::deserialize(variant).map(Transaction::Dispute),
             "resolve" => Resolve::deserialize(variant).map(Transaction::Resolve),
             "chargeback" => Chargeback::deserialize(variant).map(Transaction::Chargeback),
+            "freeze" => Freeze::deserialize(variant).map(Transaction::Freeze),
+            "unfreeze" => Unfreeze::deserialize(variant).map(Transaction::Unfreeze),
+            "transfer" => Transfer::deserialize(variant).map(Transaction::Transfer),
             other => Err(A::Error::unknown_variant(
                 other,
-                &["deposit", "withdrawal", "dispute", "resolve", "chargeback"],
+                &[
+                    "deposit",
+                    "withdrawal",
+                    "dispute",
+                    "resolve",
+                    "chargeback",
+                    "freeze",
+                    "unfreeze",
+                    "transfer",
+                ],
             )),
         }
     }
@@ -135,6 +241,8 @@ mod test {
             dispute, 1, 1,
             resolve, 1, 1,
             chargeback, 2, 2,
+            freeze, 1, 3,
+            unfreeze, 1, 4,
         "};
 
         let expected = [
@@ -160,16 +268,47 @@ mod test {
                 client: 2.into(),
                 transaction_id: 2.into(),
             }),
+            Transaction::Freeze(Freeze {
+                client: 1.into(),
+                transaction_id: 3.into(),
+            }),
+            Transaction::Unfreeze(Unfreeze {
+                client: 1.into(),
+                transaction_id: 4.into(),
+            }),
         ];
 
         let mut reader = ReaderBuilder::new()
             .trim(Trim::All)
             .from_reader(csv.as_bytes());
 
-        let iter = reader.deserialize();
+        let parsed: Vec<Transaction> = reader.deserialize().collect::<Result<_, _>>().unwrap();
 
-        for (record, expected) in iter.zip(expected) {
-            assert_eq!(expected, record.unwrap());
-        }
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_csv_transfer_deserialization() {
+        let csv = indoc::indoc! {"
+            type, client, tx, amount, to
+            deposit, 1, 1, 1.0,
+            transfer, 1, 2, 0.5, 2
+        "};
+
+        let mut reader = ReaderBuilder::new()
+            .trim(Trim::All)
+            .from_reader(csv.as_bytes());
+
+        let parsed: Vec<Transaction> = reader.deserialize().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(
+            parsed[1],
+            Transaction::Transfer(Transfer {
+                from: 1.into(),
+                transaction_id: 2.into(),
+                amount: Decimal::new(5, 1),
+                to: 2.into(),
+            })
+        );
     }
 }
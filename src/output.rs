@@ -0,0 +1,732 @@
+use std::io;
+use std::str::FromStr;
+
+use anyhow::Context;
+use csv::WriterBuilder;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+
+use crate::input::InputOptions;
+use crate::model::{AccountMemo, AccountSummary, ClientId, GrandTotals, Transaction};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    /// A single JSON array of records.
+    Json,
+    /// Right-aligned columns of [`OutputOptions::column_widths`] characters, without delimiters.
+    /// Only supported for account summaries.
+    FixedWidth,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "fixed-width" => Ok(OutputFormat::FixedWidth),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(OutputFormat::MessagePack),
+            other => anyhow::bail!("Unsupported output format `{other}`"),
+        }
+    }
+}
+
+/// How records are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    /// Whether CSV and fixed-width output start with a header row.
+    pub headers: bool,
+    pub column_widths: ColumnWidths,
+    /// Write the `locked` column of summaries as `1`/`0` instead of `true`/`false`.
+    pub numeric_locked: bool,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::default(),
+            headers: true,
+            column_widths: ColumnWidths::default(),
+            numeric_locked: false,
+        }
+    }
+}
+
+/// Widths of the summary columns in [`OutputFormat::FixedWidth`] output, parsed from
+/// `<client>,<available>,<held>,<total>,<locked>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnWidths {
+    pub client: usize,
+    pub available: usize,
+    pub held: usize,
+    pub total: usize,
+    pub locked: usize,
+}
+
+impl Default for ColumnWidths {
+    fn default() -> Self {
+        Self {
+            client: 6,
+            available: 32,
+            held: 32,
+            total: 32,
+            locked: 6,
+        }
+    }
+}
+
+impl FromStr for ColumnWidths {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let widths = s
+            .split(',')
+            .map(|width| {
+                width
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid column width `{width}`"))
+            })
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+
+        match widths[..] {
+            [client, available, held, total, locked] => Ok(Self {
+                client,
+                available,
+                held,
+                total,
+                locked,
+            }),
+            _ => anyhow::bail!("Expected 5 column widths, got `{s}`"),
+        }
+    }
+}
+
+/// Passes writes through to `inner` while hashing them with 64-bit FNV-1a, a cheap and stable
+/// fingerprint of what was written. Not suitable where tampering matters.
+pub struct HashingWriter<W> {
+    inner: W,
+    hash: u64,
+}
+
+impl<W> HashingWriter<W> {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hash: Self::OFFSET_BASIS,
+        }
+    }
+
+    /// Hash of everything written so far.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl<W: io::Write> io::Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+
+        for byte in &buf[..written] {
+            self.hash ^= u64::from(*byte);
+            self.hash = self.hash.wrapping_mul(Self::PRIME);
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Rounding of a single output column, parsed from `<decimal places>[:<mode>]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rounding {
+    pub decimal_places: u32,
+    pub strategy: RoundingStrategy,
+}
+
+impl FromStr for Rounding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (decimal_places, mode) = s.split_once(':').unwrap_or((s, "half-even"));
+
+        let strategy = match mode {
+            "half-even" => RoundingStrategy::MidpointNearestEven,
+            "half-up" => RoundingStrategy::MidpointAwayFromZero,
+            "half-down" => RoundingStrategy::MidpointTowardZero,
+            "up" => RoundingStrategy::AwayFromZero,
+            "down" => RoundingStrategy::ToZero,
+            "ceiling" => RoundingStrategy::ToPositiveInfinity,
+            "floor" => RoundingStrategy::ToNegativeInfinity,
+            other => anyhow::bail!("Unsupported rounding mode `{other}`"),
+        };
+
+        Ok(Self {
+            decimal_places: decimal_places
+                .parse()
+                .with_context(|| format!("Invalid number of decimal places `{decimal_places}`"))?,
+            strategy,
+        })
+    }
+}
+
+/// Rounding applied to the amount columns of a summary when it is written, independently per
+/// column. The balances held by the processor are not affected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SummaryRounding {
+    pub available: Option<Rounding>,
+    pub held: Option<Rounding>,
+    pub total: Option<Rounding>,
+}
+
+impl SummaryRounding {
+    pub fn apply(&self, summary: &mut AccountSummary) {
+        fn round(value: &mut Decimal, rounding: Option<Rounding>) {
+            if let Some(rounding) = rounding {
+                *value = value.round_dp_with_strategy(rounding.decimal_places, rounding.strategy);
+            }
+        }
+
+        round(&mut summary.available, self.available);
+        round(&mut summary.held, self.held);
+        round(&mut summary.total, self.total);
+    }
+}
+
+/// The negative amounts [`clamp_negative`] replaced with zero in a client's summary, zero for
+/// the columns left untouched. Kept aside for reconciliation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ClampedBalances {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+}
+
+/// Replaces negative amount columns of `summary` with zero, for consumers that reject negative
+/// balances. Returns the replaced amounts, or `None` when nothing was negative. Unlike the
+/// anomaly flag this only affects what is written, the processor balances are unchanged.
+pub fn clamp_negative(summary: &mut AccountSummary) -> Option<ClampedBalances> {
+    fn clamp(value: &mut Decimal) -> Decimal {
+        if value.is_sign_negative() && !value.is_zero() {
+            std::mem::replace(value, Decimal::ZERO)
+        } else {
+            Decimal::ZERO
+        }
+    }
+
+    let clamped = ClampedBalances {
+        client: summary.client,
+        available: clamp(&mut summary.available),
+        held: clamp(&mut summary.held),
+        total: clamp(&mut summary.total),
+    };
+
+    let untouched =
+        clamped.available.is_zero() && clamped.held.is_zero() && clamped.total.is_zero();
+
+    (!untouched).then_some(clamped)
+}
+
+/// An [`AccountSummary`] with `locked` as a number, see [`OutputOptions::numeric_locked`].
+#[derive(Serialize)]
+struct NumericLockedSummary {
+    client: ClientId,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    locked: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_deposit_disputes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_withdrawal_disputes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<AccountMemo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    processed_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deposit_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    withdrawal_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<AccountSummary> for NumericLockedSummary {
+    fn from(summary: AccountSummary) -> Self {
+        Self {
+            client: summary.client,
+            available: summary.available,
+            held: summary.held,
+            total: summary.total,
+            locked: u8::from(summary.locked),
+            open_deposit_disputes: summary.open_deposit_disputes,
+            open_withdrawal_disputes: summary.open_withdrawal_disputes,
+            memo: summary.memo,
+            processed_at: summary.processed_at,
+            version: summary.version,
+            deposit_count: summary.deposit_count,
+            withdrawal_count: summary.withdrawal_count,
+            error: summary.error,
+        }
+    }
+}
+
+pub fn write_summaries<W: io::Write>(
+    writer: W,
+    options: &OutputOptions,
+    summaries: impl IntoIterator<Item = AccountSummary>,
+) -> anyhow::Result<()> {
+    match options.format {
+        OutputFormat::FixedWidth => write_fixed_width(writer, options, summaries),
+        _ if options.numeric_locked => write_records(
+            writer,
+            options,
+            summaries.into_iter().map(NumericLockedSummary::from),
+        ),
+        _ => write_records(writer, options, summaries),
+    }
+}
+
+/// Writes `totals` as a single aggregate row.
+pub fn write_grand_totals<W: io::Write>(
+    writer: W,
+    options: &OutputOptions,
+    totals: GrandTotals,
+) -> anyhow::Result<()> {
+    write_records(writer, options, [totals])
+}
+
+/// Writes the amounts clamped by [`clamp_negative`], one row per affected client.
+pub fn write_clamped<W: io::Write>(
+    writer: W,
+    options: &OutputOptions,
+    clamped: impl IntoIterator<Item = ClampedBalances>,
+) -> anyhow::Result<()> {
+    write_records(writer, options, clamped)
+}
+
+/// Writes `transactions` in the input CSV layout described by `options`, so they can be fed back
+/// into the processor. The `to` column is only added when there are transfers.
+pub fn write_transactions<W: io::Write>(
+    writer: W,
+    options: &InputOptions,
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> anyhow::Result<()> {
+    let transactions: Vec<_> = transactions.into_iter().collect();
+    let with_recipient = transactions
+        .iter()
+        .any(|transaction| matches!(transaction, Transaction::Transfer(_)));
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(writer);
+
+    let mut headers = vec!["type", "client", "tx", "amount"];
+
+    if with_recipient {
+        headers.push("to");
+    }
+
+    writer.write_record(headers)?;
+
+    for transaction in transactions {
+        // formats the amount exactly as stored instead of going through a float
+        let amount = transaction
+            .amount()
+            .map(|amount| {
+                amount
+                    .to_string()
+                    .replace('.', &options.decimal_separator.to_string())
+            })
+            .unwrap_or_default();
+
+        let mut record = vec![
+            transaction.type_name().to_owned(),
+            transaction.client_id().to_string(),
+            transaction.tx_id().to_string(),
+            amount,
+        ];
+
+        if with_recipient {
+            record.push(match &transaction {
+                Transaction::Transfer(transfer) => transfer.to.to_string(),
+                _ => String::new(),
+            });
+        }
+
+        writer
+            .write_record(record)
+            .context("Failed writing transaction")?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_fixed_width<W: io::Write>(
+    mut writer: W,
+    options: &OutputOptions,
+    summaries: impl IntoIterator<Item = AccountSummary>,
+) -> anyhow::Result<()> {
+    const COLUMNS: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+    let ColumnWidths {
+        client,
+        available,
+        held,
+        total,
+        locked,
+    } = options.column_widths;
+    let widths = [client, available, held, total, locked];
+
+    let mut write_line = |values: [String; 5]| -> anyhow::Result<()> {
+        let mut line = String::new();
+
+        for ((column, value), width) in COLUMNS.into_iter().zip(values).zip(widths) {
+            // truncating would silently misreport the balance
+            if value.chars().count() > width {
+                anyhow::bail!("`{value}` does not fit the {width} characters of column `{column}`");
+            }
+
+            line.push_str(&format!("{value:>width$}"));
+        }
+
+        writeln!(writer, "{line}").context("Failed producing output")
+    };
+
+    if options.headers {
+        write_line(COLUMNS.map(str::to_owned))?;
+    }
+
+    for summary in summaries {
+        write_line([
+            summary.client.to_string(),
+            summary.available.to_string(),
+            summary.held.to_string(),
+            summary.total.to_string(),
+            if options.numeric_locked {
+                u8::from(summary.locked).to_string()
+            } else {
+                summary.locked.to_string()
+            },
+        ])?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_records<W: io::Write, T: Serialize>(
+    writer: W,
+    options: &OutputOptions,
+    records: impl IntoIterator<Item = T>,
+) -> anyhow::Result<()> {
+    match options.format {
+        OutputFormat::Csv => {
+            let mut writer = WriterBuilder::new()
+                .has_headers(options.headers)
+                .from_writer(writer);
+
+            for record in records {
+                writer
+                    .serialize(record)
+                    .context("Failed producing output")?;
+            }
+
+            writer.flush()?;
+        }
+        OutputFormat::Json => {
+            let mut writer = writer;
+            let records: Vec<_> = records.into_iter().collect();
+
+            serde_json::to_writer_pretty(&mut writer, &records)
+                .context("Failed producing output")?;
+            writeln!(writer)?;
+            writer.flush()?;
+        }
+        OutputFormat::FixedWidth => {
+            anyhow::bail!("Fixed-width output is only supported for account summaries")
+        }
+        #[cfg(feature = "msgpack")]
+        OutputFormat::MessagePack => {
+            let mut writer = writer;
+            let records: Vec<_> = records.into_iter().collect();
+
+            // named encoding keeps the optional columns unambiguous for consumers
+            rmp_serde::encode::write_named(&mut writer, &records)
+                .context("Failed producing output")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::input;
+    use crate::processor::TransactionProcessor;
+
+    fn summary(client: u16, available: Decimal, held: Decimal, locked: bool) -> AccountSummary {
+        AccountSummary {
+            client: client.into(),
+            available,
+            held,
+            total: available + held,
+            locked,
+            open_deposit_disputes: None,
+            open_withdrawal_disputes: None,
+            memo: None,
+            processed_at: None,
+            version: None,
+            deposit_count: None,
+            withdrawal_count: None,
+            error: None,
+            anomalous: false,
+        }
+    }
+
+    #[test]
+    fn test_rounding_per_column() {
+        let rounding = SummaryRounding {
+            available: None,
+            held: Some("2:up".parse().unwrap()),
+            total: Some("3:down".parse().unwrap()),
+        };
+
+        let mut summary = summary(1, Decimal::new(123456, 5), Decimal::new(123451, 5), false);
+
+        rounding.apply(&mut summary);
+
+        assert_eq!(summary.available, Decimal::new(123456, 5));
+        assert_eq!(summary.held, Decimal::new(124, 2));
+        assert_eq!(summary.total, Decimal::new(2469, 3));
+    }
+
+    #[test]
+    fn test_clamp_negative_balances() {
+        let mut summary = summary(1, Decimal::new(-50, 1), Decimal::new(20, 1), true);
+
+        let clamped = clamp_negative(&mut summary);
+
+        assert_eq!(summary.available, Decimal::ZERO);
+        assert_eq!(summary.held, Decimal::new(20, 1));
+        assert_eq!(summary.total, Decimal::ZERO);
+        assert_eq!(
+            clamped,
+            Some(ClampedBalances {
+                client: 1.into(),
+                available: Decimal::new(-50, 1),
+                held: Decimal::ZERO,
+                total: Decimal::new(-30, 1),
+            })
+        );
+
+        assert_eq!(clamp_negative(&mut summary), None);
+    }
+
+    #[test]
+    fn test_optional_headers() {
+        let totals = || GrandTotals {
+            clients: 1,
+            ..Default::default()
+        };
+
+        let mut with_headers = Vec::new();
+        write_grand_totals(&mut with_headers, &OutputOptions::default(), totals()).unwrap();
+
+        let mut without_headers = Vec::new();
+        let options = OutputOptions {
+            headers: false,
+            ..Default::default()
+        };
+        write_grand_totals(&mut without_headers, &options, totals()).unwrap();
+
+        let with_headers = String::from_utf8(with_headers).unwrap();
+        let without_headers = String::from_utf8(without_headers).unwrap();
+
+        assert!(with_headers.starts_with("clients,available,held,total,locked_accounts\n"));
+        assert_eq!(with_headers.lines().count(), 2);
+        assert_eq!(without_headers.lines().count(), 1);
+        assert_eq!(with_headers.lines().nth(1), without_headers.lines().next());
+    }
+
+    #[test]
+    fn test_hashing_writer() {
+        use std::io::Write;
+
+        let mut writer = HashingWriter::new(Vec::new());
+
+        assert_eq!(writer.hash(), 0xcbf2_9ce4_8422_2325);
+
+        writer.write_all(b"a").unwrap();
+
+        assert_eq!(writer.hash(), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(writer.inner, b"a");
+    }
+
+    #[test]
+    fn test_json_output() {
+        let mut buffer = Vec::new();
+        let options = OutputOptions {
+            format: OutputFormat::Json,
+            ..Default::default()
+        };
+
+        write_summaries(
+            &mut buffer,
+            &options,
+            [summary(1, Decimal::new(15, 1), Decimal::ZERO, true)],
+        )
+        .unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+
+        assert_eq!(value[0]["locked"], serde_json::Value::Bool(true));
+        assert_eq!(value[0]["total"].as_f64(), Some(1.5));
+        assert_eq!(value.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_numeric_locked() {
+        let locked = || summary(1, Decimal::ZERO, Decimal::ZERO, true);
+
+        let mut boolean = Vec::new();
+        write_summaries(&mut boolean, &OutputOptions::default(), [locked()]).unwrap();
+
+        let mut numeric = Vec::new();
+        let options = OutputOptions {
+            numeric_locked: true,
+            ..Default::default()
+        };
+        write_summaries(&mut numeric, &options, [locked()]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(boolean).unwrap(),
+            "client,available,held,total,locked\n1,0.0,0.0,0.0,true\n"
+        );
+        assert_eq!(
+            String::from_utf8(numeric).unwrap(),
+            "client,available,held,total,locked\n1,0.0,0.0,0.0,1\n"
+        );
+    }
+
+    #[test]
+    fn test_fixed_width_layout() {
+        let negative_held = || summary(12, Decimal::new(15, 1), Decimal::new(-25, 2), false);
+
+        let mut options = OutputOptions {
+            format: OutputFormat::FixedWidth,
+            column_widths: "6,10,6,6,7".parse().unwrap(),
+            ..Default::default()
+        };
+
+        let mut buffer = Vec::new();
+        write_summaries(&mut buffer, &options, [negative_held()]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            concat!(
+                "client available  held total locked\n",
+                "    12       1.5 -0.25  1.25  false\n",
+            )
+        );
+
+        options.column_widths.held = 4;
+
+        let error = write_summaries(&mut Vec::new(), &options, [negative_held()]).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`-0.25` does not fit the 4 characters of column `held`"
+        );
+    }
+
+    #[test]
+    fn test_rejected_transactions_can_be_reprocessed() {
+        let csv = indoc::indoc! {"
+            type, client, tx, amount
+            deposit, 1, 1, 1.0
+            withdrawal, 1, 2, 2.5
+        "};
+
+        let options = InputOptions::default();
+        let mut processor = TransactionProcessor::default().with_rejected_retention(true);
+
+        for tx in input::transactions(csv.as_bytes(), &options) {
+            let _ = processor.handle(tx.unwrap());
+        }
+
+        let mut rejects = Vec::new();
+        write_transactions(&mut rejects, &options, processor.take_rejected()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(rejects.clone()).unwrap(),
+            "type,client,tx,amount\nwithdrawal,1,2,2.5\n"
+        );
+
+        let mut processor =
+            TransactionProcessor::default().with_opening_balances(HashMap::from([(
+                1.into(),
+                (Decimal::new(50, 1), Decimal::ZERO, false),
+            )]));
+
+        for tx in input::transactions(rejects.as_slice(), &options) {
+            let _ = processor.handle(tx.unwrap());
+        }
+
+        let summary = processor.summary().next().unwrap();
+
+        assert_eq!(summary.available, Decimal::new(25, 1));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_round_trip() {
+        let summaries = || {
+            vec![
+                summary(1, Decimal::new(15, 1), Decimal::new(5, 1), false),
+                AccountSummary {
+                    open_deposit_disputes: Some(0),
+                    open_withdrawal_disputes: Some(1),
+                    memo: Some(crate::model::AccountMemo::Locked),
+                    ..summary(2, Decimal::ZERO, Decimal::ZERO, true)
+                },
+            ]
+        };
+
+        let mut buffer = Vec::new();
+        let options = OutputOptions {
+            format: OutputFormat::MessagePack,
+            ..Default::default()
+        };
+
+        write_summaries(&mut buffer, &options, summaries()).unwrap();
+
+        let decoded: Vec<AccountSummary> = rmp_serde::from_slice(&buffer).unwrap();
+
+        assert_eq!(decoded, summaries());
+    }
+}
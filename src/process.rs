@@ -0,0 +1,52 @@
+//! Reading transactions into a [`TransactionProcessor`], the loop behind the `tp` binary.
+
+use std::io;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::input::{self, InputOptions};
+use crate::model::Transaction;
+use crate::processor::TransactionProcessor;
+
+/// Handles the transactions of the CSV read by `reader` with a default processor. Rejected
+/// transactions are recorded in the processor's error log, only an unreadable record fails.
+pub fn process_reader<R: io::Read>(reader: R) -> anyhow::Result<TransactionProcessor> {
+    let mut processor = TransactionProcessor::default();
+
+    process_records(
+        &mut processor,
+        input::read(reader, &InputOptions::default()),
+    )?;
+
+    Ok(processor)
+}
+
+/// Like [`process_reader`], for the file at `path`.
+pub fn process_path(path: &Path) -> anyhow::Result<TransactionProcessor> {
+    let records = input::read_path(path, &InputOptions::default())
+        .with_context(|| format!("Failed opening `{}`", path.display()))?;
+    let mut processor = TransactionProcessor::default();
+
+    process_records(&mut processor, records)?;
+
+    Ok(processor)
+}
+
+/// Handles each of `records` with `processor` in order, returning how many were handled.
+pub fn process_records(
+    processor: &mut TransactionProcessor,
+    records: impl IntoIterator<Item = anyhow::Result<Transaction>>,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+
+    for record in records {
+        let transaction = record.context("Failed parsing file")?;
+
+        count += 1;
+        // rejections are kept in the processor's error log
+        let _ = processor.handle(transaction);
+    }
+
+    Ok(count)
+}
@@ -1,12 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use rust_decimal::Decimal;
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
-use crate::model::{Account, AccountSummary, ClientId, Transaction, TransactionId};
+use crate::model::{
+    Account, AccountMemo, AccountSummary, Chargeback, ClientId, Deposit, GrandTotals, Transaction,
+    TransactionId, TransactionType, Transfer, Withdrawal,
+};
 
-#[derive(Debug, Error)]
-#[cfg_attr(test, derive(PartialEq))]
+mod config;
+mod snapshot;
+
+pub use config::{CeilingPolicy, ExcessPrecision, Limits, ProcessorConfig, WithdrawalChargeback};
+pub use snapshot::{AccountSnapshot, ProcessorSnapshot, TransactionSnapshot};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum ProcessingErrorKind {
     #[error("Cannot execute transactions with negative amount")]
     NegativeAmount,
@@ -20,356 +29,4452 @@ pub enum ProcessingErrorKind {
     ResolveWhenTxNotUnderDispute,
     #[error("Cannot chargeback transaction when not under dispute")]
     ChargebackWhenTxNotUnderDispute,
+    #[error("Client reached the maximum number of transactions")]
+    ClientTransactionLimit,
+    #[error("Transaction was disputed and resolved too many times")]
+    ExcessiveDisputeCycling,
+    #[error("Dispute would exceed the limit on funds held across all accounts")]
+    GlobalHeldLimitExceeded,
+    #[error("Deposit would exceed the account balance ceiling")]
+    BalanceCeilingExceeded,
+    #[error("Cannot resolve dispute holding no funds")]
+    ZeroValueResolve,
+    #[error("Client has no opened account")]
+    UnknownClient,
+    #[error("Account reached the maximum number of open disputes")]
+    TooManyOpenDisputes,
+    #[error("Amount has more decimal places than the currency allows")]
+    ExcessPrecision,
+    #[error("Resolve would release more funds than the account holds")]
+    ResolveExceedsHeld,
+    #[error("Cannot chargeback transaction whose dispute was already resolved")]
+    ChargebackAfterResolve,
+    #[error("Transaction type is not accepted")]
+    TransactionTypeNotAllowed,
+    #[error("Dispute amount is out of proportion to the account's deposits")]
+    DisputeExceedsHistory,
+    #[error("Referenced transaction belongs to another client")]
+    ClientMismatch,
+    #[error("Releasing the disputed funds would leave the account's held funds negative")]
+    HeldUnderflow,
+    #[error("Account is too new for withdrawals")]
+    AccountTooNew,
+    #[error("Transaction id was already used")]
+    DuplicateTransactionId,
+    #[error("Amount would overflow the account balance")]
+    AmountOverflow,
+    #[error("Amount is written with more decimal places than allowed")]
+    TooManyDecimalPlaces,
+    #[error("Dispute of a withdrawal on an account that never received funds")]
+    DisputeBeforeAnyFunds,
+    #[error("Transaction was disputed the maximum number of times")]
+    DisputeLimitExceeded,
+    #[error("Transfer recipient account is locked")]
+    TransferRecipientUnavailable,
+    #[error("Cannot transfer funds to the sending client")]
+    SelfTransfer,
+    #[error("Withdrawals cannot be disputed")]
+    WithdrawalDisputesDisabled,
+}
+
+impl ProcessingErrorKind {
+    /// Every kind, to look one up by its code.
+    const ALL: [Self; 29] = [
+        Self::NegativeAmount,
+        Self::NotSufficientFunds,
+        Self::DisputeReferencesAlreadyDisputedTx,
+        Self::NotSufficientFundsForDispute,
+        Self::ResolveWhenTxNotUnderDispute,
+        Self::ChargebackWhenTxNotUnderDispute,
+        Self::ClientTransactionLimit,
+        Self::ExcessiveDisputeCycling,
+        Self::GlobalHeldLimitExceeded,
+        Self::BalanceCeilingExceeded,
+        Self::ZeroValueResolve,
+        Self::UnknownClient,
+        Self::TooManyOpenDisputes,
+        Self::ExcessPrecision,
+        Self::ResolveExceedsHeld,
+        Self::ChargebackAfterResolve,
+        Self::TransactionTypeNotAllowed,
+        Self::DisputeExceedsHistory,
+        Self::ClientMismatch,
+        Self::HeldUnderflow,
+        Self::AccountTooNew,
+        Self::DuplicateTransactionId,
+        Self::AmountOverflow,
+        Self::DisputeBeforeAnyFunds,
+        Self::DisputeLimitExceeded,
+        Self::TransferRecipientUnavailable,
+        Self::WithdrawalDisputesDisabled,
+        Self::SelfTransfer,
+        Self::TooManyDecimalPlaces,
+    ];
+
+    /// Stable machine-readable identifier of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NegativeAmount => "NEGATIVE_AMOUNT",
+            Self::NotSufficientFunds => "NOT_SUFFICIENT_FUNDS",
+            Self::DisputeReferencesAlreadyDisputedTx => "DISPUTE_REFERENCES_ALREADY_DISPUTED_TX",
+            Self::NotSufficientFundsForDispute => "NOT_SUFFICIENT_FUNDS_FOR_DISPUTE",
+            Self::ResolveWhenTxNotUnderDispute => "RESOLVE_WHEN_TX_NOT_UNDER_DISPUTE",
+            Self::ChargebackWhenTxNotUnderDispute => "CHARGEBACK_WHEN_TX_NOT_UNDER_DISPUTE",
+            Self::ClientTransactionLimit => "CLIENT_TRANSACTION_LIMIT",
+            Self::ExcessiveDisputeCycling => "EXCESSIVE_DISPUTE_CYCLING",
+            Self::GlobalHeldLimitExceeded => "GLOBAL_HELD_LIMIT_EXCEEDED",
+            Self::BalanceCeilingExceeded => "BALANCE_CEILING_EXCEEDED",
+            Self::ZeroValueResolve => "ZERO_VALUE_RESOLVE",
+            Self::UnknownClient => "UNKNOWN_CLIENT",
+            Self::TooManyOpenDisputes => "TOO_MANY_OPEN_DISPUTES",
+            Self::ExcessPrecision => "EXCESS_PRECISION",
+            Self::ResolveExceedsHeld => "RESOLVE_EXCEEDS_HELD",
+            Self::ChargebackAfterResolve => "CHARGEBACK_AFTER_RESOLVE",
+            Self::TransactionTypeNotAllowed => "TRANSACTION_TYPE_NOT_ALLOWED",
+            Self::DisputeExceedsHistory => "DISPUTE_EXCEEDS_HISTORY",
+            Self::ClientMismatch => "CLIENT_MISMATCH",
+            Self::HeldUnderflow => "HELD_UNDERFLOW",
+            Self::AccountTooNew => "ACCOUNT_TOO_NEW",
+            Self::DuplicateTransactionId => "DUPLICATE_TRANSACTION_ID",
+            Self::AmountOverflow => "AMOUNT_OVERFLOW",
+            Self::TooManyDecimalPlaces => "TOO_MANY_DECIMAL_PLACES",
+            Self::DisputeBeforeAnyFunds => "DISPUTE_BEFORE_ANY_FUNDS",
+            Self::DisputeLimitExceeded => "DISPUTE_LIMIT_EXCEEDED",
+            Self::TransferRecipientUnavailable => "TRANSFER_RECIPIENT_UNAVAILABLE",
+            Self::SelfTransfer => "SELF_TRANSFER",
+            Self::WithdrawalDisputesDisabled => "WITHDRAWAL_DISPUTES_DISABLED",
+        }
+    }
+}
+
+/// Serializes as `{ "code": ..., "message": ... }`, the message being the `Display` output.
+impl Serialize for ProcessingErrorKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ProcessingErrorKind", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+/// Deserializes what [`Serialize`] wrote, the kind is looked up by its code.
+impl<'de> Deserialize<'de> for ProcessingErrorKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Serialized {
+            code: String,
+        }
+
+        let Serialized { code } = Serialized::deserialize(deserializer)?;
+
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.code() == code)
+            .ok_or_else(|| de::Error::custom(format!("unknown error code `{code}`")))
+    }
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
 #[error("client={client} tx={tx}. Error: {kind}")]
 pub struct ProcessingError {
     client: ClientId,
     tx: TransactionId,
     kind: ProcessingErrorKind,
+    occurrences: usize,
+}
+
+impl ProcessingError {
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+
+    pub fn tx(&self) -> TransactionId {
+        self.tx
+    }
+
+    pub fn kind(&self) -> &ProcessingErrorKind {
+        &self.kind
+    }
+
+    /// How many consecutive times the client hit this error kind, always `1` unless
+    /// [`ProcessorConfig::collapse_repeated_errors`] is enabled. `tx` refers to the first one.
+    pub fn occurrences(&self) -> usize {
+        self.occurrences
+    }
+}
+
+/// Stably reorders `batch` so deposits and withdrawals come first, then disputes, then resolves
+/// and chargebacks, keeping the relative order within each group.
+///
+/// This changes semantics: a dispute that preceded its deposit in the input, and would be
+/// dropped as dangling, applies once sorted. Likewise a withdrawal may now see funds a later
+/// deposit brought in.
+pub fn sort_by_type_priority(batch: &mut [Transaction]) {
+    batch.sort_by_key(|tx| match tx {
+        Transaction::Deposit(_)
+        | Transaction::Withdrawal(_)
+        | Transaction::Transfer(_)
+        | Transaction::Freeze(_)
+        | Transaction::Unfreeze(_) => 0,
+        Transaction::Dispute(_) => 1,
+        Transaction::Resolve(_) | Transaction::Chargeback(_) => 2,
+    });
 }
 
 #[derive(Default)]
 pub struct TransactionProcessor {
+    config: ProcessorConfig,
     accounts: HashMap<ClientId, Account>,
     transactions: HashMap<TransactionId, TransactionState>,
+    /// Clients that were credited funds at some point, kept when their account is closed.
+    funded_clients: HashSet<ClientId>,
+    errors: Vec<ProcessingError>,
+    /// Clients rejected while they had no account to record the error on, e.g. unknown clients in
+    /// strict mode.
+    rejected_without_account: HashSet<ClientId>,
+    rejected: Vec<Transaction>,
+    total_held: Decimal,
+    /// Transactions applied so far. Kept apart from the per-account counts, which go with a
+    /// cleared or closed account.
+    processed_count: usize,
+    rejected_count: usize,
+    /// Transactions handled so far, rejected ones included. Measures account age.
+    sequence: usize,
+    threshold_hook: Option<ThresholdHook>,
+    lock_policy: Option<LockPolicy>,
+}
+
+/// A client whose `total` differs from an external ledger, see [`TransactionProcessor::reconcile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub client: ClientId,
+    /// Total according to the ledger.
+    pub expected: Decimal,
+    /// Total according to the processor.
+    pub actual: Decimal,
+}
+
+/// A client whose total can't be compared against the ledger as the arithmetic overflows, see
+/// [`TransactionProcessor::reconcile`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Total of client {client} overflows when reconciling")]
+pub struct ReconcileOverflow {
+    pub client: ClientId,
+}
+
+/// Operational counters over everything handled so far, see [`TransactionProcessor::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessingStats {
+    /// Accounts known to the processor.
+    pub accounts: usize,
+    pub locked_accounts: usize,
+    pub total_held: Decimal,
+    /// Transactions applied to an account, a transfer counting once.
+    pub processed: usize,
+    /// Transactions rejected with an error.
+    pub rejected: usize,
+}
+
+struct ThresholdHook {
+    threshold: Decimal,
+    callback: Box<dyn FnMut(ClientId, Decimal)>,
+}
+
+type LockPolicy = Box<dyn Fn(&Account, &Chargeback, Decimal) -> bool>;
+
+/// A transaction currently under dispute, as exchanged by [`TransactionProcessor::disputes`]
+/// and [`TransactionProcessor::with_disputes`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisputedTransaction {
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub amount: Decimal,
+    pub is_deposit: bool,
 }
 
 #[derive(Debug)]
 struct TransactionState {
+    client: ClientId,
     amount: Decimal,
     is_under_dispute: bool,
     is_deposit: bool,
+    resolve_count: u32,
+    dispute_count: u32,
+    /// Transfers are only stored to reserve their id, they can't be disputed.
+    is_transfer: bool,
 }
 
-impl TransactionProcessor {
-    pub fn handle(&mut self, tx: Transaction) {
-        let account = self.accounts.entry(tx.client_id()).or_default();
+/// The parts of a transfer recipient's account checked before the sender is debited.
+#[derive(Debug, Clone, Copy, Default)]
+struct Recipient {
+    available: Decimal,
+    held: Decimal,
+    total_deposited: Decimal,
+    transaction_count: usize,
+}
+
+impl ThresholdHook {
+    /// Calls the callback when moving from `previous_total` to `total` crosses the threshold.
+    fn check(&mut self, client: ClientId, previous_total: Decimal, total: Decimal) {
+        if (previous_total < self.threshold) != (total < self.threshold) {
+            (self.callback)(client, total);
+        }
+    }
+}
+
+impl TransactionProcessor {
+    /// A processor with the default configuration continuing from `snapshot`, e.g. a checkpoint
+    /// of a long-running ingest taken with [`TransactionProcessor::snapshot`]. Accounts keep their
+    /// errors, the error log starts empty.
+    pub fn restore(snapshot: ProcessorSnapshot) -> Self {
+        Self::default().with_snapshot(snapshot)
+    }
+
+    /// Restores the accounts and transactions of a [`ProcessorSnapshot`] taken at the end of a
+    /// previous run, so processing continues where it stopped.
+    pub fn with_snapshot(mut self, snapshot: ProcessorSnapshot) -> Self {
+        self.sequence = snapshot.sequence;
+        self.rejected_without_account
+            .extend(snapshot.rejected_without_account);
+
+        for account in snapshot.accounts {
+            self.total_held += account.held;
+            self.accounts.insert(
+                account.client,
+                Account {
+                    available: account.available,
+                    held: account.held,
+                    locked: account.locked,
+                    errors: account.errors,
+                    open_deposit_disputes: account.open_deposit_disputes,
+                    open_withdrawal_disputes: account.open_withdrawal_disputes,
+                    transaction_count: account.transaction_count,
+                    deposit_count: account.deposit_count,
+                    withdrawal_count: account.withdrawal_count,
+                    total_deposited: account.total_deposited,
+                    opened_at: account.opened_at,
+                },
+            );
+            self.funded_clients.insert(account.client);
+        }
+
+        for tx in snapshot.transactions {
+            if tx.is_deposit {
+                self.funded_clients.insert(tx.client);
+            }
+
+            self.transactions.insert(
+                tx.tx,
+                TransactionState {
+                    client: tx.client,
+                    amount: tx.amount,
+                    is_under_dispute: tx.is_under_dispute,
+                    is_deposit: tx.is_deposit,
+                    resolve_count: tx.resolve_count,
+                    dispute_count: tx.disputes(),
+                    is_transfer: tx.is_transfer,
+                },
+            );
+        }
+
+        self
+    }
+
+    /// Seeds the processor with transactions already under dispute, e.g. exported from another
+    /// processor by [`TransactionProcessor::disputes`], so they can later be resolved or charged
+    /// back. Balances are left untouched: the disputed funds are expected to be part of the held
+    /// balance seeded through [`TransactionProcessor::with_opening_balances`].
+    pub fn with_disputes(
+        mut self,
+        disputes: impl IntoIterator<Item = DisputedTransaction>,
+    ) -> Self {
+        for dispute in disputes {
+            let account = self.accounts.entry(dispute.client).or_default();
+            self.funded_clients.insert(dispute.client);
+
+            if dispute.is_deposit {
+                account.open_deposit_disputes += 1;
+            } else {
+                account.open_withdrawal_disputes += 1;
+            }
+
+            self.transactions.insert(
+                dispute.tx,
+                TransactionState {
+                    client: dispute.client,
+                    amount: dispute.amount,
+                    is_under_dispute: true,
+                    is_deposit: dispute.is_deposit,
+                    resolve_count: 0,
+                    dispute_count: 1,
+                    is_transfer: false,
+                },
+            );
+        }
+
+        self
+    }
+
+    /// Seeds the processor with accounts carried over from a prior period, given as
+    /// `(available, held, locked)` per client. Seeded accounts behave as if they already existed.
+    pub fn with_opening_balances(
+        mut self,
+        balances: HashMap<ClientId, (Decimal, Decimal, bool)>,
+    ) -> Self {
+        for (client, (available, held, locked)) in balances {
+            self.total_held += held;
+            self.funded_clients.insert(client);
+            self.accounts.insert(
+                client,
+                Account {
+                    available,
+                    held,
+                    locked,
+                    ..Default::default()
+                },
+            );
+        }
+
+        self
+    }
+
+    /// Adds `open_deposit_disputes`/`open_withdrawal_disputes` columns to the summary.
+    pub fn with_open_disputes_report(mut self, enabled: bool) -> Self {
+        self.config.report_open_disputes = enabled;
+        self
+    }
+
+    /// Adds the first error of each account to the summary, see [`ProcessorConfig::include_errors`].
+    pub fn with_errors_in_summary(mut self, enabled: bool) -> Self {
+        self.config.include_errors = enabled;
+        self
+    }
+
+    /// Adds `deposit_count`/`withdrawal_count` columns to the end of the summary.
+    pub fn with_transaction_counts_report(mut self, enabled: bool) -> Self {
+        self.config.report_transaction_counts = enabled;
+        self
+    }
+
+    /// Skips every further transaction of an account once one of its transactions was rejected.
+    pub fn with_freeze_on_error(mut self, enabled: bool) -> Self {
+        self.config.freeze_on_error = enabled;
+        self
+    }
+
+    /// Collapses consecutive identical error kinds of a client into a single logged error.
+    pub fn with_collapsed_errors(mut self, enabled: bool) -> Self {
+        self.config.collapse_repeated_errors = enabled;
+        self
+    }
+
+    /// Processes resolves on locked accounts and unlocks an account once its last open dispute
+    /// is resolved.
+    pub fn with_auto_unlock_on_last_resolve(mut self, enabled: bool) -> Self {
+        self.config.auto_unlock_on_last_resolve = enabled;
+        self
+    }
+
+    /// Adds a `memo` column summarizing the state of each account.
+    pub fn with_memo(mut self, enabled: bool) -> Self {
+        self.config.include_memo = enabled;
+        self
+    }
+
+    /// Rejects transactions for a client once `limit` of them have been applied.
+    pub fn with_max_transactions_per_client(mut self, limit: usize) -> Self {
+        self.config.limits.max_transactions_per_client = Some(limit);
+        self
+    }
+
+    /// Allows withdrawals to be covered by funds held in dispute, see
+    /// [`ProcessorConfig::allow_withdrawal_from_held`].
+    pub fn with_withdrawal_from_held(mut self, allowed: bool) -> Self {
+        self.config.allow_withdrawal_from_held = allowed;
+        self
+    }
+
+    /// Reports a dispute as [`ProcessingErrorKind::ExcessiveDisputeCycling`] once the referenced
+    /// transaction has already been disputed and resolved `cycles` times.
+    pub fn with_max_dispute_cycles(mut self, cycles: u32) -> Self {
+        self.config.limits.max_dispute_cycles = Some(cycles);
+        self
+    }
+
+    /// Rejects a dispute with [`ProcessingErrorKind::TooManyOpenDisputes`] while the account
+    /// already has `max_open` disputes open.
+    pub fn with_max_open_disputes_per_account(mut self, max_open: usize) -> Self {
+        self.config.limits.max_open_disputes_per_account = Some(max_open);
+        self
+    }
+
+    /// Caps the funds held in dispute across all accounts at `max_held`.
+    pub fn with_max_total_held(mut self, max_held: Decimal) -> Self {
+        self.config.max_total_held = Some(max_held);
+        self
+    }
+
+    /// Retains transactions that resulted in an error, see [`TransactionProcessor::take_rejected`].
+    pub fn with_rejected_retention(mut self, enabled: bool) -> Self {
+        self.config.retain_rejected = enabled;
+        self
+    }
+
+    /// Enforces a ceiling on each account's `total` when depositing.
+    pub fn with_balance_ceiling(mut self, max_total: Decimal, policy: CeilingPolicy) -> Self {
+        self.config.limits.max_total = Some(max_total);
+        self.config.ceiling_policy = policy;
+        self
+    }
+
+    /// Limits amounts to `max_scale` decimal places, handling deposits and withdrawals with more
+    /// precision according to their own policy.
+    pub fn with_max_scale(
+        mut self,
+        max_scale: u32,
+        deposits: ExcessPrecision,
+        withdrawals: ExcessPrecision,
+    ) -> Self {
+        self.config.max_scale = Some(max_scale);
+        self.config.deposit_excess_precision = deposits;
+        self.config.withdrawal_excess_precision = withdrawals;
+        self
+    }
+
+    /// Rejects amounts written with more than `max_scale` decimal places as
+    /// [`ProcessingErrorKind::TooManyDecimalPlaces`], see [`ProcessorConfig::max_decimal_places`].
+    pub fn with_scale(mut self, max_scale: u32) -> Self {
+        self.config.max_decimal_places = Some(max_scale);
+        self
+    }
+
+    /// Overrides the limits for a single client. Fields left unset fall back to the global limits.
+    pub fn with_client_limits(mut self, client: ClientId, limits: Limits) -> Self {
+        self.config.client_limits.insert(client, limits);
+        self
+    }
+
+    /// Selects how a chargeback of a disputed withdrawal is settled.
+    pub fn with_withdrawal_chargeback(mut self, settlement: WithdrawalChargeback) -> Self {
+        self.config.withdrawal_chargeback = settlement;
+        self
+    }
+
+    /// Strips trailing zeros from stored transaction amounts and summarized balances.
+    pub fn with_normalized_amounts(mut self, enabled: bool) -> Self {
+        self.config.normalize_amounts = enabled;
+        self
+    }
+
+    /// Rejects a dispute with [`ProcessingErrorKind::DisputeExceedsHistory`] when its amount
+    /// exceeds `multiple` times the deposits the account received so far.
+    pub fn with_max_dispute_to_deposits(mut self, multiple: Decimal) -> Self {
+        self.config.max_dispute_to_deposits = Some(multiple);
+        self
+    }
+
+    /// Accepts only transactions of the given types, rejecting any other with
+    /// [`ProcessingErrorKind::TransactionTypeNotAllowed`].
+    pub fn with_allowed_types(mut self, types: impl IntoIterator<Item = TransactionType>) -> Self {
+        self.config.allowed_types = Some(types.into_iter().collect());
+        self
+    }
+
+    /// Never locks accounts, see [`ProcessorConfig::sandbox`].
+    pub fn with_sandbox(mut self, enabled: bool) -> Self {
+        self.config.sandbox = enabled;
+        self
+    }
+
+    /// Removes accounts that return to a zero balance, see
+    /// [`ProcessorConfig::close_zero_accounts`].
+    pub fn with_zero_account_closing(mut self, enabled: bool) -> Self {
+        self.config.close_zero_accounts = enabled;
+        self
+    }
+
+    /// Reports chargebacks of transactions whose dispute was resolved as
+    /// [`ProcessingErrorKind::ChargebackAfterResolve`].
+    pub fn with_chargeback_after_resolve_reporting(mut self, enabled: bool) -> Self {
+        self.config.report_chargeback_after_resolve = enabled;
+        self
+    }
+
+    /// Reports resolves that would release more than the account holds as
+    /// [`ProcessingErrorKind::ResolveExceedsHeld`], see [`ProcessorConfig::guard_held_release`].
+    pub fn with_held_release_guard(mut self, enabled: bool) -> Self {
+        self.config.guard_held_release = enabled;
+        self
+    }
+
+    /// Rejects deposits and withdrawals of clients without an account opened through
+    /// [`TransactionProcessor::with_opening_balances`] instead of opening one on the fly.
+    pub fn with_strict_clients(mut self, enabled: bool) -> Self {
+        self.config.strict_clients = enabled;
+        self
+    }
+
+    /// Rejects resolves of disputes that hold no funds, leaving the dispute open.
+    pub fn with_zero_value_resolve_rejection(mut self, enabled: bool) -> Self {
+        self.config.reject_zero_value_resolves = enabled;
+        self
+    }
+
+    /// Whether withdrawals may be disputed, see [`ProcessorConfig::allow_withdrawal_disputes`].
+    pub fn with_withdrawal_disputes_allowed(mut self, allowed: bool) -> Self {
+        self.config.allow_withdrawal_disputes = allowed;
+        self
+    }
+
+    /// Rejects disputes of withdrawals on accounts that never received funds, see
+    /// [`ProcessorConfig::reject_disputes_before_funds`].
+    pub fn with_dispute_before_funds_rejection(mut self, enabled: bool) -> Self {
+        self.config.reject_disputes_before_funds = enabled;
+        self
+    }
+
+    /// Limits how often a single transaction may be disputed, see
+    /// [`ProcessorConfig::max_disputes_per_tx`].
+    pub fn with_max_disputes_per_tx(mut self, max_disputes: u32) -> Self {
+        self.config.max_disputes_per_tx = Some(max_disputes);
+        self
+    }
+
+    /// Rejects withdrawals and outgoing transfers of accounts opened less than `transactions`
+    /// transactions ago, see [`ProcessorConfig::min_account_age`].
+    pub fn with_min_account_age(mut self, transactions: usize) -> Self {
+        self.config.min_account_age = Some(transactions);
+        self
+    }
+
+    /// Rounds the funds held for disputes to `scale` decimal places, see
+    /// [`ProcessorConfig::dispute_hold_scale`].
+    pub fn with_dispute_hold_scale(mut self, scale: u32) -> Self {
+        self.config.dispute_hold_scale = Some(scale);
+        self
+    }
+
+    /// Leaves withdrawal dispute holds out of the summarized `total`, see
+    /// [`ProcessorConfig::exclude_withdrawal_holds_from_total`].
+    pub fn with_withdrawal_holds_excluded_from_total(mut self, enabled: bool) -> Self {
+        self.config.exclude_withdrawal_holds_from_total = enabled;
+        self
+    }
+
+    /// Decides per chargeback whether the account gets locked, in place of always locking it.
+    /// `policy` is called with the account after the chargeback was applied, the chargeback and
+    /// the charged back amount. Has no effect in sandbox mode, where accounts are never locked.
+    pub fn with_lock_policy(
+        mut self,
+        policy: impl Fn(&Account, &Chargeback, Decimal) -> bool + 'static,
+    ) -> Self {
+        self.lock_policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Calls `callback` with the client and its new total whenever a transaction moves an
+    /// account's total across `threshold`, upwards (reaching it) or downwards (falling below it).
+    pub fn with_threshold_hook(
+        mut self,
+        threshold: Decimal,
+        callback: impl FnMut(ClientId, Decimal) + 'static,
+    ) -> Self {
+        self.threshold_hook = Some(ThresholdHook {
+            threshold,
+            callback: Box::new(callback),
+        });
+        self
+    }
+
+    /// Applies `tx` to its client's account. A rejected transaction is returned as an error, as
+    /// well as logged in [`TransactionProcessor::errors`]. Transactions that are skipped rather
+    /// than rejected, such as those of a locked account or referencing an unknown transaction,
+    /// return `Ok`.
+    pub fn handle(&mut self, mut tx: Transaction) -> Result<(), ProcessingError> {
+        let sequence = self.sequence;
+        self.sequence += 1;
+
+        if let Some(allowed) = &self.config.allowed_types {
+            if !allowed.contains(&tx.transaction_type()) {
+                return self.reject(&tx, ProcessingErrorKind::TransactionTypeNotAllowed);
+            }
+        }
+
+        if self.config.strict_clients && !self.accounts.contains_key(&tx.client_id()) {
+            if let Transaction::Deposit(_) | Transaction::Withdrawal(_) | Transaction::Transfer(_) =
+                tx
+            {
+                return self.reject(&tx, ProcessingErrorKind::UnknownClient);
+            }
+
+            return Ok(());
+        }
+
+        // referencing an unknown transaction, or a transfer, is skipped before the account lookup,
+        // so a client that only ever appeared in such a transaction doesn't end up with an empty
+        // account
+        if let Transaction::Dispute(_) | Transaction::Resolve(_) | Transaction::Chargeback(_) = tx {
+            let disputable = self
+                .transactions
+                .get(&tx.tx_id())
+                .is_some_and(|state| !state.is_transfer);
+
+            if !disputable {
+                return Ok(());
+            }
+        }
+
+        // the recipient of a transfer is looked up before the sender's account is borrowed, and only
+        // credited once the sender's side went through
+        let (recipient, recipient_limits) = match &tx {
+            Transaction::Transfer(transfer) => (
+                self.recipient(transfer.to),
+                self.config.limits_for(transfer.to),
+            ),
+            _ => (Ok(Recipient::default()), Limits::default()),
+        };
+
+        let limits = self.config.limits_for(tx.client_id());
+        let account = self
+            .accounts
+            .entry(tx.client_id())
+            .or_insert_with(|| Account {
+                opened_at: Some(sequence),
+                ..Default::default()
+            });
+
+        let unlocking_resolve =
+            self.config.auto_unlock_on_last_resolve && matches!(tx, Transaction::Resolve(_));
+        let unlocking = unlocking_resolve || matches!(tx, Transaction::Unfreeze(_));
+
+        // we skip processing an account that has been locked, errors only block it when configured.
+        // This is also what absorbs a repeated chargeback: the first one locked the account, so the
+        // second never reaches the dispute state check and is dropped without an error
+        if (account.locked && !unlocking)
+            || (self.config.freeze_on_error && !account.errors.is_empty())
+        {
+            return Ok(());
+        }
+
+        // balances are updated with checked arithmetic so adversarial amounts are rejected as
+        // `AmountOverflow` instead of panicking, derived sums only used for comparisons saturate
+        let previous_total = account.available.saturating_add(account.held);
+
+        // administrative freezes are not the client's transactions, so they are not counted
+        let administrative = matches!(tx, Transaction::Freeze(_) | Transaction::Unfreeze(_));
+
+        if let Some(limit) = limits.max_transactions_per_client {
+            if !administrative && account.transaction_count >= limit {
+                return self.reject(&tx, ProcessingErrorKind::ClientTransactionLimit);
+            }
+        }
+
+        // storing it would overwrite the earlier transaction, which later disputes refer to
+        if let Transaction::Deposit(_) | Transaction::Withdrawal(_) | Transaction::Transfer(_) = tx
+        {
+            if self.transactions.contains_key(&tx.tx_id()) {
+                return self.reject(&tx, ProcessingErrorKind::DuplicateTransactionId);
+            }
+        }
+
+        if self.config.normalize_amounts {
+            if let Transaction::Deposit(Deposit { amount, .. })
+            | Transaction::Withdrawal(Withdrawal { amount, .. })
+            | Transaction::Transfer(Transfer { amount, .. }) = &mut tx
+            {
+                *amount = amount.normalize();
+            }
+        }
+
+        match &mut tx {
+            Transaction::Deposit(deposit) => {
+                match checked_amount(
+                    deposit.amount,
+                    &self.config,
+                    self.config.deposit_excess_precision,
+                ) {
+                    Ok(amount) => deposit.amount = amount,
+                    Err(kind) => return self.reject(&tx, kind),
+                }
+
+                let total = account.available.saturating_add(account.held);
+
+                match ceiling_credit(total, deposit.amount, &limits, self.config.ceiling_policy) {
+                    Ok(amount) => deposit.amount = amount,
+                    Err(kind) => return self.reject(&tx, kind),
+                }
+
+                let (Some(available), Some(total_deposited)) = (
+                    account.available.checked_add(deposit.amount),
+                    account.total_deposited.checked_add(deposit.amount),
+                ) else {
+                    return self.reject(&tx, ProcessingErrorKind::AmountOverflow);
+                };
+
+                account.available = available;
+                account.total_deposited = total_deposited;
+                account.deposit_count += 1;
+                self.funded_clients.insert(deposit.client);
+            }
+            Transaction::Withdrawal(withdrawal) => {
+                match checked_amount(
+                    withdrawal.amount,
+                    &self.config,
+                    self.config.withdrawal_excess_precision,
+                ) {
+                    Ok(amount) => withdrawal.amount = amount,
+                    Err(kind) => return self.reject(&tx, kind),
+                }
+
+                if let (Some(min_age), Some(opened_at)) =
+                    (self.config.min_account_age, account.opened_at)
+                {
+                    if sequence - opened_at < min_age {
+                        return self.reject(&tx, ProcessingErrorKind::AccountTooNew);
+                    }
+                }
+
+                let spendable = if self.config.allow_withdrawal_from_held {
+                    account.available.saturating_add(account.held)
+                } else {
+                    account.available
+                };
+
+                if withdrawal.amount > spendable {
+                    return self.reject(&tx, ProcessingErrorKind::NotSufficientFunds);
+                }
+
+                let Some(available) = account.available.checked_sub(withdrawal.amount) else {
+                    return self.reject(&tx, ProcessingErrorKind::AmountOverflow);
+                };
+
+                account.available = available;
+                account.withdrawal_count += 1;
+            }
+            Transaction::Dispute(dispute) => {
+                let Some(tx_state) = self.transactions.get_mut(&dispute.transaction_id) else {
+                    return Ok(());
+                };
+
+                if tx_state.client != dispute.client {
+                    return self.reject(&tx, ProcessingErrorKind::ClientMismatch);
+                }
+
+                if tx_state.is_under_dispute {
+                    return self
+                        .reject(&tx, ProcessingErrorKind::DisputeReferencesAlreadyDisputedTx);
+                }
+
+                if !self.config.allow_withdrawal_disputes && !tx_state.is_deposit {
+                    return self.reject(&tx, ProcessingErrorKind::WithdrawalDisputesDisabled);
+                }
+
+                let never_funded = !self.funded_clients.contains(&dispute.client);
+
+                if self.config.reject_disputes_before_funds && !tx_state.is_deposit && never_funded
+                {
+                    return self.reject(&tx, ProcessingErrorKind::DisputeBeforeAnyFunds);
+                }
+
+                if let Some(max_disputes) = self.config.max_disputes_per_tx {
+                    if tx_state.dispute_count >= max_disputes {
+                        return self.reject(&tx, ProcessingErrorKind::DisputeLimitExceeded);
+                    }
+                }
+
+                if let Some(max_cycles) = limits.max_dispute_cycles {
+                    if tx_state.resolve_count >= max_cycles {
+                        return self.reject(&tx, ProcessingErrorKind::ExcessiveDisputeCycling);
+                    }
+                }
+
+                if let Some(max_open) = limits.max_open_disputes_per_account {
+                    if account.open_deposit_disputes + account.open_withdrawal_disputes >= max_open
+                    {
+                        return self.reject(&tx, ProcessingErrorKind::TooManyOpenDisputes);
+                    }
+                }
+
+                if let Some(multiple) = self.config.max_dispute_to_deposits {
+                    if tx_state.amount > account.total_deposited.saturating_mul(multiple) {
+                        return self.reject(&tx, ProcessingErrorKind::DisputeExceedsHistory);
+                    }
+                }
+
+                let hold = self.config.dispute_hold(tx_state.amount);
+
+                let (Some(held), Some(total_held)) = (
+                    account.held.checked_add(hold),
+                    self.total_held.checked_add(hold),
+                ) else {
+                    return self.reject(&tx, ProcessingErrorKind::AmountOverflow);
+                };
+
+                if let Some(max_held) = self.config.max_total_held {
+                    if total_held > max_held {
+                        return self.reject(&tx, ProcessingErrorKind::GlobalHeldLimitExceeded);
+                    }
+                }
+
+                if tx_state.is_deposit {
+                    if hold > account.available {
+                        return self.reject(&tx, ProcessingErrorKind::NotSufficientFundsForDispute);
+                    }
+
+                    account.available -= hold;
+                    account.open_deposit_disputes += 1;
+                } else {
+                    account.open_withdrawal_disputes += 1;
+                }
+
+                account.held = held;
+                tx_state.is_under_dispute = true;
+                tx_state.dispute_count += 1;
+                self.total_held = total_held;
+            }
+            Transaction::Resolve(resolve) => {
+                let Some(tx_state) = self.transactions.get_mut(&resolve.transaction_id) else {
+                    return Ok(());
+                };
+
+                if tx_state.client != resolve.client {
+                    return self.reject(&tx, ProcessingErrorKind::ClientMismatch);
+                }
+
+                if !tx_state.is_under_dispute {
+                    return self.reject(&tx, ProcessingErrorKind::ResolveWhenTxNotUnderDispute);
+                }
+
+                if self.config.reject_zero_value_resolves && tx_state.amount.is_zero() {
+                    return self.reject(&tx, ProcessingErrorKind::ZeroValueResolve);
+                }
+
+                let hold = self.config.dispute_hold(tx_state.amount);
+
+                // processing alone keeps held funds covering every open dispute, seeded balances
+                // and disputes may not
+                if hold > account.held {
+                    let kind = if self.config.guard_held_release {
+                        ProcessingErrorKind::ResolveExceedsHeld
+                    } else {
+                        ProcessingErrorKind::HeldUnderflow
+                    };
+
+                    return self.reject(&tx, kind);
+                }
+
+                // a disputed withdrawal never left `available`, so nothing goes back to it
+                let available = if tx_state.is_deposit {
+                    account.available.checked_add(hold)
+                } else {
+                    Some(account.available)
+                };
+
+                let Some(available) = available else {
+                    return self.reject(&tx, ProcessingErrorKind::AmountOverflow);
+                };
+
+                account.available = available;
+                account.held -= hold;
+                account.close_dispute(tx_state.is_deposit);
+
+                if unlocking_resolve
+                    && account.open_deposit_disputes + account.open_withdrawal_disputes == 0
+                {
+                    account.locked = false;
+                }
+
+                tx_state.is_under_dispute = false;
+                tx_state.resolve_count += 1;
+                self.total_held -= hold;
+            }
+            Transaction::Chargeback(chargeback) => {
+                let Some(tx_state) = self.transactions.get_mut(&chargeback.transaction_id) else {
+                    return Ok(());
+                };
+
+                if tx_state.client != chargeback.client {
+                    return self.reject(&tx, ProcessingErrorKind::ClientMismatch);
+                }
+
+                // only reachable for an already charged back transaction when the account stays
+                // unlocked, i.e. in sandbox mode or when the lock policy declined
+                if !tx_state.is_under_dispute {
+                    let kind = if self.config.report_chargeback_after_resolve
+                        && tx_state.resolve_count > 0
+                    {
+                        ProcessingErrorKind::ChargebackAfterResolve
+                    } else {
+                        ProcessingErrorKind::ChargebackWhenTxNotUnderDispute
+                    };
+
+                    return self.reject(&tx, kind);
+                }
+
+                let hold = self.config.dispute_hold(tx_state.amount);
+
+                if hold > account.held {
+                    return self.reject(&tx, ProcessingErrorKind::HeldUnderflow);
+                }
+
+                let available = if tx_state.is_deposit {
+                    // settles the rounding difference left in `available` by the dispute
+                    account.available.checked_sub(tx_state.amount - hold)
+                } else if self.config.withdrawal_chargeback == WithdrawalChargeback::Refund {
+                    account.available.checked_add(tx_state.amount)
+                } else {
+                    Some(account.available)
+                };
+
+                let Some(available) = available else {
+                    return self.reject(&tx, ProcessingErrorKind::AmountOverflow);
+                };
+
+                account.available = available;
+                account.held -= hold;
+
+                account.close_dispute(tx_state.is_deposit);
+
+                let lock = match &self.lock_policy {
+                    Some(policy) => policy(account, chargeback, tx_state.amount),
+                    None => true,
+                };
+
+                if lock && !self.config.sandbox {
+                    account.locked = true;
+                }
+
+                tx_state.is_under_dispute = false;
+                self.total_held -= hold;
+            }
+            Transaction::Transfer(transfer) => {
+                match checked_amount(
+                    transfer.amount,
+                    &self.config,
+                    self.config.withdrawal_excess_precision,
+                ) {
+                    Ok(amount) => transfer.amount = amount,
+                    Err(kind) => return self.reject(&tx, kind),
+                }
+
+                // a transfer debits the sender like a withdrawal
+                if let (Some(min_age), Some(opened_at)) =
+                    (self.config.min_account_age, account.opened_at)
+                {
+                    if sequence - opened_at < min_age {
+                        return self.reject(&tx, ProcessingErrorKind::AccountTooNew);
+                    }
+                }
+
+                if transfer.to == transfer.from {
+                    return self.reject(&tx, ProcessingErrorKind::SelfTransfer);
+                }
+
+                // the recipient is credited like a deposit, so it gets the same checks
+                let recipient = match recipient {
+                    Ok(recipient) => recipient,
+                    Err(kind) => return self.reject(&tx, kind),
+                };
+
+                if let Some(limit) = recipient_limits.max_transactions_per_client {
+                    if recipient.transaction_count >= limit {
+                        return self.reject(&tx, ProcessingErrorKind::ClientTransactionLimit);
+                    }
+                }
+
+                let recipient_total = recipient.available.saturating_add(recipient.held);
+
+                match ceiling_credit(
+                    recipient_total,
+                    transfer.amount,
+                    &recipient_limits,
+                    self.config.ceiling_policy,
+                ) {
+                    Ok(amount) => transfer.amount = amount,
+                    Err(kind) => return self.reject(&tx, kind),
+                }
+
+                if transfer.amount > account.available {
+                    return self.reject(&tx, ProcessingErrorKind::NotSufficientFunds);
+                }
+
+                let (Some(available), Some(_), Some(_)) = (
+                    account.available.checked_sub(transfer.amount),
+                    recipient.available.checked_add(transfer.amount),
+                    recipient.total_deposited.checked_add(transfer.amount),
+                ) else {
+                    return self.reject(&tx, ProcessingErrorKind::AmountOverflow);
+                };
+
+                account.available = available;
+            }
+            Transaction::Freeze(_) => account.locked = true,
+            Transaction::Unfreeze(_) => account.locked = false,
+        }
+
+        if !administrative {
+            account.transaction_count += 1;
+            self.processed_count += 1;
+        }
+
+        if let Some(hook) = &mut self.threshold_hook {
+            let total = account.available.saturating_add(account.held);
+            hook.check(tx.client_id(), previous_total, total);
+        }
+
+        let closable = self.config.close_zero_accounts
+            && account.available.is_zero()
+            && account.held.is_zero()
+            && !account.locked
+            && account.errors.is_empty()
+            && account.open_deposit_disputes + account.open_withdrawal_disputes == 0;
+
+        if closable {
+            self.accounts.remove(&tx.client_id());
+        }
+
+        if let Transaction::Transfer(transfer) = &tx {
+            let recipient = self.accounts.entry(transfer.to).or_insert_with(|| Account {
+                opened_at: Some(sequence),
+                ..Default::default()
+            });
+
+            let previous_total = recipient.available.saturating_add(recipient.held);
+
+            recipient.available += transfer.amount;
+            recipient.total_deposited += transfer.amount;
+            recipient.deposit_count += 1;
+            recipient.transaction_count += 1;
+            self.funded_clients.insert(transfer.to);
+
+            if let Some(hook) = &mut self.threshold_hook {
+                let total = recipient.available.saturating_add(recipient.held);
+                hook.check(transfer.to, previous_total, total);
+            }
+        }
+
+        self.add_transaction(tx);
+
+        Ok(())
+    }
+
+    /// Handles `batch` in order, returning the outcome of each transaction at its position.
+    pub fn handle_batch(&mut self, batch: Vec<Transaction>) -> Vec<Result<(), ProcessingError>> {
+        batch.into_iter().map(|tx| self.handle(tx)).collect()
+    }
+
+    /// Erases `client`: its account, stored transactions, logged errors and retained rejects.
+    /// Later transactions of the client start from a fresh account and can no longer reference
+    /// the erased ones. Returns whether anything was removed.
+    pub fn clear_account(&mut self, client: ClientId) -> bool {
+        let account = self.accounts.remove(&client);
+
+        if let Some(account) = &account {
+            self.total_held -= account.held;
+        }
+
+        let counts = (
+            self.transactions.len(),
+            self.errors.len(),
+            self.rejected.len(),
+        );
+
+        self.transactions.retain(|_, state| state.client != client);
+        self.funded_clients.remove(&client);
+        let rejected_without_account = self.rejected_without_account.remove(&client);
+        self.errors.retain(|error| error.client != client);
+        self.rejected.retain(|tx| tx.client_id() != client);
+
+        account.is_some()
+            || rejected_without_account
+            || counts
+                != (
+                    self.transactions.len(),
+                    self.errors.len(),
+                    self.rejected.len(),
+                )
+    }
+
+    /// Whether any account ended up with an error, or a transaction was rejected for a client
+    /// without an account.
+    pub fn had_errors(&self) -> bool {
+        !self.rejected_without_account.is_empty()
+            || self
+                .accounts
+                .values()
+                .any(|account| !account.errors.is_empty())
+    }
+
+    /// The effective configuration, as set up by the `with_*` methods.
+    pub fn config(&self) -> &ProcessorConfig {
+        &self.config
+    }
+
+    /// The current state of `client`'s account, `None` for unknown clients. Unlike
+    /// [`TransactionProcessor::summary`] this leaves the processor usable.
+    pub fn account(&self, client: ClientId) -> Option<AccountSnapshot> {
+        self.accounts
+            .get(&client)
+            .map(|account| account_snapshot(client, account))
+    }
+
+    /// Whether `client`'s account is locked, `None` for unknown clients.
+    pub fn is_locked(&self, client: ClientId) -> Option<bool> {
+        self.accounts.get(&client).map(|account| account.locked)
+    }
+
+    /// The errors logged so far, in the order they occurred.
+    pub fn errors(&self) -> impl Iterator<Item = &ProcessingError> {
+        self.errors.iter()
+    }
+
+    /// Drains the errors recorded since the last call. Accounts keep their own error state.
+    pub fn take_errors(&mut self) -> Vec<ProcessingError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Consistency check run after processing: the clients holding funds without any of their
+    /// transactions under dispute, in ascending order. Processing never produces that state on its
+    /// own, so a non-empty result indicates a bug in the engine, or held funds seeded through
+    /// [`TransactionProcessor::with_opening_balances`] without the matching disputes.
+    pub fn inconsistent_accounts(&self) -> Vec<ClientId> {
+        let disputing: HashSet<_> = self
+            .transactions
+            .values()
+            .filter(|state| state.is_under_dispute)
+            .map(|state| state.client)
+            .collect();
+
+        let mut clients: Vec<_> = self
+            .accounts
+            .iter()
+            .filter(|(client, account)| {
+                account.held > Decimal::ZERO && !disputing.contains(*client)
+            })
+            .map(|(&client, _)| client)
+            .collect();
+
+        clients.sort_unstable();
+        clients
+    }
+
+    /// Captures every account, with its errors, and stored transaction, ordered by client and
+    /// transaction id, to be restored with [`TransactionProcessor::with_snapshot`]. The error log
+    /// is not part of it.
+    pub fn snapshot(&self) -> ProcessorSnapshot {
+        let mut accounts: Vec<_> = self
+            .accounts
+            .iter()
+            .map(|(&client, account)| account_snapshot(client, account))
+            .collect();
+
+        let mut transactions: Vec<_> = self
+            .transactions
+            .iter()
+            .map(|(&tx, state)| TransactionSnapshot {
+                client: state.client,
+                tx,
+                amount: state.amount,
+                is_deposit: state.is_deposit,
+                is_under_dispute: state.is_under_dispute,
+                resolve_count: state.resolve_count,
+                dispute_count: state.dispute_count,
+                is_transfer: state.is_transfer,
+            })
+            .collect();
+
+        let mut rejected_without_account: Vec<_> =
+            self.rejected_without_account.iter().copied().collect();
+
+        accounts.sort_unstable_by_key(|account| account.client);
+        transactions.sort_unstable_by_key(|tx| tx.tx);
+        rejected_without_account.sort_unstable();
+
+        ProcessorSnapshot {
+            accounts,
+            transactions,
+            sequence: self.sequence,
+            rejected_without_account,
+        }
+    }
+
+    /// The transactions currently under dispute, in no particular order.
+    pub fn disputes(&self) -> Vec<DisputedTransaction> {
+        self.transactions
+            .iter()
+            .filter(|(_, state)| state.is_under_dispute)
+            .map(|(&tx, state)| DisputedTransaction {
+                client: state.client,
+                tx,
+                amount: state.amount,
+                is_deposit: state.is_deposit,
+            })
+            .collect()
+    }
+
+    /// Drains the transactions rejected with an error since the last call. Only populated when
+    /// [`ProcessorConfig::retain_rejected`] is enabled.
+    pub fn take_rejected(&mut self) -> Vec<Transaction> {
+        std::mem::take(&mut self.rejected)
+    }
+
+    /// Sums the balances of every account, as reported by [`TransactionProcessor::summary`].
+    pub fn grand_totals(&self) -> GrandTotals {
+        self.accounts
+            .values()
+            .fold(GrandTotals::default(), |mut totals, account| {
+                let total = account.available.checked_add(account.held);
+                let overflows = totals.available.checked_add(account.available).is_none()
+                    || totals.held.checked_add(account.held).is_none()
+                    || total
+                        .and_then(|total| totals.total.checked_add(total))
+                        .is_none();
+
+                totals.clients += 1;
+                totals.available = totals.available.saturating_add(account.available);
+                totals.held = totals.held.saturating_add(account.held);
+                totals.total = totals
+                    .total
+                    .saturating_add(account.available.saturating_add(account.held));
+                totals.locked_accounts += usize::from(account.locked);
+                totals.anomalous |= overflows;
+                totals
+            })
+    }
+
+    /// Rough estimate of the memory held by the processor, for capacity planning. Counts the
+    /// allocated capacity of its collections, plus one control byte per hash map slot, but not
+    /// allocator overhead.
+    pub fn estimated_memory_bytes(&self) -> usize {
+        use std::mem::size_of;
+
+        size_of::<Self>()
+            + self.accounts.capacity() * (size_of::<(ClientId, Account)>() + 1)
+            + self.transactions.capacity() * (size_of::<(TransactionId, TransactionState)>() + 1)
+            + self.funded_clients.capacity() * (size_of::<ClientId>() + 1)
+            + self.rejected_without_account.capacity() * (size_of::<ClientId>() + 1)
+            + self.errors.capacity() * size_of::<ProcessingError>()
+            + self.rejected.capacity() * size_of::<Transaction>()
+    }
+
+    /// Counters for monitoring, complementing the balances of
+    /// [`TransactionProcessor::grand_totals`].
+    pub fn stats(&self) -> ProcessingStats {
+        ProcessingStats {
+            accounts: self.accounts.len(),
+            locked_accounts: self
+                .accounts
+                .values()
+                .filter(|account| account.locked)
+                .count(),
+            total_held: self.total_held,
+            processed: self.processed_count,
+            rejected: self.rejected_count,
+        }
+    }
+
+    /// Compares the totals of every account against an external `ledger`, returning the
+    /// clients differing by more than `tolerance`, ordered by client. A client missing on either
+    /// side counts as a total of zero there. Fails for a client whose total, or its difference to
+    /// the ledger, overflows.
+    pub fn reconcile(
+        &self,
+        ledger: &HashMap<ClientId, Decimal>,
+        tolerance: Decimal,
+    ) -> Result<Vec<Mismatch>, ReconcileOverflow> {
+        let totals = self
+            .accounts
+            .iter()
+            .map(|(&client, account)| {
+                let total = account.available.checked_add(account.held);
+
+                total
+                    .map(|total| (client, total))
+                    .ok_or(ReconcileOverflow { client })
+            })
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        let clients: HashSet<_> = totals.keys().chain(ledger.keys()).copied().collect();
+        let mut mismatches = Vec::new();
+
+        for client in clients {
+            let mismatch = Mismatch {
+                client,
+                expected: ledger.get(&client).copied().unwrap_or_default(),
+                actual: totals.get(&client).copied().unwrap_or_default(),
+            };
+
+            let difference = mismatch
+                .expected
+                .checked_sub(mismatch.actual)
+                .ok_or(ReconcileOverflow { client })?;
+
+            if difference.abs() > tolerance {
+                mismatches.push(mismatch);
+            }
+        }
+
+        mismatches.sort_unstable_by_key(|mismatch| mismatch.client);
+        Ok(mismatches)
+    }
+
+    /// Spendable funds across every account, excluding anything held in dispute. `None` when the
+    /// sum overflows.
+    pub fn net_available(&self) -> Option<Decimal> {
+        self.accounts
+            .values()
+            .try_fold(Decimal::ZERO, |sum, account| {
+                sum.checked_add(account.available)
+            })
+    }
+
+    /// Summaries of every account, including those with an error, ordered by client.
+    pub fn summary(self) -> impl Iterator<Item = AccountSummary> {
+        self.sorted_summaries().into_iter()
+    }
+
+    /// Like [`TransactionProcessor::summary`], leaving the processor usable, e.g. to report
+    /// periodically while processing continues.
+    pub fn summaries(&self) -> impl Iterator<Item = AccountSummary> + '_ {
+        self.sorted_summaries().into_iter()
+    }
+
+    fn sorted_summaries(&self) -> Vec<AccountSummary> {
+        let withdrawal_holds = self.withdrawal_holds();
+
+        let mut summaries: Vec<_> = self
+            .accounts
+            .iter()
+            .map(|(&client, account)| {
+                let excluded = withdrawal_holds.get(&client).copied().unwrap_or_default();
+
+                summarize(&self.config, client, account, excluded)
+            })
+            .collect();
+
+        summaries.sort_unstable_by_key(|summary| summary.client);
+        summaries
+    }
+
+    /// Summaries for `clients` in the requested order. Unknown clients are skipped.
+    pub fn summary_for_clients(&self, clients: &[ClientId]) -> Vec<AccountSummary> {
+        let withdrawal_holds = self.withdrawal_holds();
+
+        clients
+            .iter()
+            .filter_map(|client| {
+                let excluded = withdrawal_holds.get(client).copied().unwrap_or_default();
+
+                self.accounts
+                    .get(client)
+                    .map(|account| summarize(&self.config, *client, account, excluded))
+            })
+            .collect()
+    }
+
+    /// Funds held per client for withdrawal disputes, when they are to be left out of the
+    /// summarized `total`. Empty otherwise, as accounts only track their held funds as a whole.
+    fn withdrawal_holds(&self) -> HashMap<ClientId, Decimal> {
+        let mut holds = HashMap::new();
+
+        if self.config.exclude_withdrawal_holds_from_total {
+            for state in self.transactions.values() {
+                if state.is_under_dispute && !state.is_deposit {
+                    *holds.entry(state.client).or_default() +=
+                        self.config.dispute_hold(state.amount);
+                }
+            }
+        }
+
+        holds
+    }
+
+    /// The account a transfer to `client` credits, or why it can't receive the transfer.
+    fn recipient(&self, client: ClientId) -> Result<Recipient, ProcessingErrorKind> {
+        match self.accounts.get(&client) {
+            Some(account)
+                if account.locked
+                    || (self.config.freeze_on_error && !account.errors.is_empty()) =>
+            {
+                Err(ProcessingErrorKind::TransferRecipientUnavailable)
+            }
+            Some(account) => Ok(Recipient {
+                available: account.available,
+                held: account.held,
+                total_deposited: account.total_deposited,
+                transaction_count: account.transaction_count,
+            }),
+            None if self.config.strict_clients => Err(ProcessingErrorKind::UnknownClient),
+            None => Ok(Recipient::default()),
+        }
+    }
+
+    fn reject(
+        &mut self,
+        tx: &Transaction,
+        kind: ProcessingErrorKind,
+    ) -> Result<(), ProcessingError> {
+        let error = ProcessingError {
+            client: tx.client_id(),
+            tx: tx.tx_id(),
+            kind,
+            occurrences: 1,
+        };
+
+        self.rejected_count += 1;
+
+        if self.config.retain_rejected {
+            self.rejected.push(tx.clone());
+        }
+
+        // unknown clients in strict mode have no account to record it on
+        match self.accounts.get_mut(&error.client) {
+            Some(account) => account.errors.push(error.clone()),
+            None => {
+                self.rejected_without_account.insert(error.client);
+            }
+        }
+
+        self.log_error(error.clone());
+
+        Err(error)
+    }
+
+    fn log_error(&mut self, error: ProcessingError) {
+        if self.config.collapse_repeated_errors {
+            let last = self
+                .errors
+                .iter_mut()
+                .rev()
+                .find(|logged| logged.client == error.client);
+
+            if let Some(last) = last.filter(|last| last.kind == error.kind) {
+                last.occurrences += 1;
+                return;
+            }
+        }
+
+        self.errors.push(error);
+    }
+
+    fn add_transaction(&mut self, tx: Transaction) {
+        let tx_id = tx.tx_id();
+
+        let state = match tx {
+            Transaction::Deposit(deposit) => TransactionState {
+                client: deposit.client,
+                amount: deposit.amount,
+                is_under_dispute: false,
+                is_deposit: true,
+                resolve_count: 0,
+                dispute_count: 0,
+                is_transfer: false,
+            },
+            Transaction::Withdrawal(withdrawal) => TransactionState {
+                client: withdrawal.client,
+                amount: withdrawal.amount,
+                is_under_dispute: false,
+                is_deposit: false,
+                resolve_count: 0,
+                dispute_count: 0,
+                is_transfer: false,
+            },
+            Transaction::Transfer(transfer) => TransactionState {
+                client: transfer.from,
+                amount: transfer.amount,
+                is_under_dispute: false,
+                is_deposit: false,
+                resolve_count: 0,
+                dispute_count: 0,
+                is_transfer: true,
+            },
+            Transaction::Dispute(_)
+            | Transaction::Resolve(_)
+            | Transaction::Chargeback(_)
+            | Transaction::Freeze(_)
+            | Transaction::Unfreeze(_) => return,
+        };
+
+        self.transactions.insert(tx_id, state);
+    }
+}
+
+/// `amount` after the checks of every transaction moving funds: it can't be negative or written
+/// with more than `max_decimal_places`, and `excess_precision` decides about one with more than
+/// `max_scale` decimal places.
+fn checked_amount(
+    amount: Decimal,
+    config: &ProcessorConfig,
+    excess_precision: ExcessPrecision,
+) -> Result<Decimal, ProcessingErrorKind> {
+    if amount < Decimal::ZERO {
+        return Err(ProcessingErrorKind::NegativeAmount);
+    }
+
+    if config
+        .max_decimal_places
+        .is_some_and(|max| amount.scale() > max)
+    {
+        return Err(ProcessingErrorKind::TooManyDecimalPlaces);
+    }
+
+    let Some(max_scale) = config.max_scale else {
+        return Ok(amount);
+    };
+
+    let rounded = amount.round_dp(max_scale);
+
+    if rounded == amount {
+        return Ok(amount);
+    }
+
+    match excess_precision {
+        ExcessPrecision::Reject => Err(ProcessingErrorKind::ExcessPrecision),
+        ExcessPrecision::Round => Ok(rounded),
+    }
+}
+
+/// The amount a deposit of `amount` credits to an account holding `total`, after the balance
+/// ceiling in `limits`.
+fn ceiling_credit(
+    total: Decimal,
+    amount: Decimal,
+    limits: &Limits,
+    policy: CeilingPolicy,
+) -> Result<Decimal, ProcessingErrorKind> {
+    let Some(max_total) = limits.max_total else {
+        return Ok(amount);
+    };
+
+    let room = max_total.saturating_sub(total);
+
+    if amount <= room {
+        return Ok(amount);
+    }
+
+    match policy {
+        CeilingPolicy::Reject => Err(ProcessingErrorKind::BalanceCeilingExceeded),
+        // the capped amount is what gets stored, so a dispute reverses only what was credited
+        CeilingPolicy::Cap => Ok(room.max(Decimal::ZERO)),
+    }
+}
+
+fn account_snapshot(client: ClientId, account: &Account) -> AccountSnapshot {
+    AccountSnapshot {
+        client,
+        available: account.available,
+        held: account.held,
+        locked: account.locked,
+        open_deposit_disputes: account.open_deposit_disputes,
+        open_withdrawal_disputes: account.open_withdrawal_disputes,
+        transaction_count: account.transaction_count,
+        deposit_count: account.deposit_count,
+        withdrawal_count: account.withdrawal_count,
+        total_deposited: account.total_deposited,
+        errors: account.errors.clone(),
+        opened_at: account.opened_at,
+    }
+}
+
+/// Decimal places of the amounts in an [`AccountSummary`].
+const OUTPUT_SCALE: u32 = 4;
+
+/// `excluded` is the part of `held` left out of `total`.
+fn summarize(
+    config: &ProcessorConfig,
+    client: ClientId,
+    account: &Account,
+    excluded: Decimal,
+) -> AccountSummary {
+    // only what is reported is rounded, balances keep their full precision
+    let normalize = |value: Decimal| {
+        let value = value.round_dp(OUTPUT_SCALE);
+
+        if config.normalize_amounts {
+            value.normalize()
+        } else {
+            value
+        }
+    };
+
+    let available = normalize(account.available);
+    let held = normalize(account.held);
+    let report_open_disputes = config.report_open_disputes;
+    let report_transaction_counts = config.report_transaction_counts;
+
+    let (total, anomalous) = match available.checked_add(held - excluded) {
+        Some(total) => (normalize(total), false),
+        None if held.is_sign_negative() => (Decimal::MIN, true),
+        None => (Decimal::MAX, true),
+    };
+
+    let memo = if anomalous {
+        AccountMemo::Anomalous
+    } else {
+        AccountMemo::of(account)
+    };
+
+    AccountSummary {
+        client,
+        available,
+        held,
+        total,
+        locked: account.locked,
+        open_deposit_disputes: report_open_disputes.then_some(account.open_deposit_disputes),
+        open_withdrawal_disputes: report_open_disputes.then_some(account.open_withdrawal_disputes),
+        memo: config.include_memo.then_some(memo),
+        processed_at: None,
+        version: None,
+        deposit_count: report_transaction_counts.then_some(account.deposit_count),
+        withdrawal_count: report_transaction_counts.then_some(account.withdrawal_count),
+        error: config.include_errors.then(|| {
+            account
+                .errors
+                .first()
+                .map(|error| error.kind().to_string())
+                .unwrap_or_default()
+        }),
+        anomalous,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use rust_decimal::Decimal;
+
+    use crate::{
+        model::{
+            Chargeback, ClientId, Deposit, Dispute, Freeze, Resolve, Transaction, TransactionId,
+            Transfer, Unfreeze, Withdrawal,
+        },
+        processor::TransactionProcessor,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_trying_to_revert_withdrawn_funds_locks_account() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(15, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(15, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(15, 1)),
+            dispute(1.into(), 1.into()),
+            chargeback(1.into(), 1.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        dbg!(&processor.accounts[&ClientId::from(1)]);
+
+        let summary = processor.summary().next().unwrap();
+
+        assert_eq!(summary.client, 1.into());
+        assert!(summary.locked);
+        assert_eq!(summary.total, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_chargeback_locks_account() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 2.into(), Decimal::new(15, 1)),
+            dispute(1.into(), 2.into()),
+            chargeback(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor.accounts[&ClientId::from(1)].errors.is_empty());
+
+        let summary = processor.summary().next().unwrap();
+
+        assert_eq!(summary.client, 1.into());
+        assert!(summary.locked);
+        assert_eq!(summary.total, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_dispute_withdrawal() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 2.into(), Decimal::new(155, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(50, 1)),
+            dispute(1.into(), 3.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor.accounts[&ClientId::from(1)].errors.is_empty());
+
+        let summary = processor.summary().next().unwrap();
+
+        assert_eq!(summary.client, 1.into());
+        assert!(!summary.locked);
+        assert_eq!(summary.available, Decimal::new(105, 1));
+        assert_eq!(summary.held, Decimal::new(50, 1));
+    }
+
+    #[test]
+    fn test_resolve_fails_if_transaction_not_under_dispute() {
+        let mut processor = TransactionProcessor::default();
+
+        let _ = processor.handle(deposit(1.into(), 2.into(), Decimal::new(15, 1)));
+        let _ = processor.handle(resolve(1.into(), 2.into()));
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::ResolveWhenTxNotUnderDispute,
+        );
+    }
+
+    #[test]
+    fn test_dispute_fails_if_not_sufficient_funds() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 2.into(), Decimal::new(15, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(5, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+        let _ = processor.handle(dispute(1.into(), 2.into()));
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::NotSufficientFundsForDispute,
+        );
+    }
+
+    #[test]
+    fn test_returns_error_on_transactions_with_negative_amounts() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 2.into(), Decimal::new(-10, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(-5, 1)),
+        ] {
+            let _ = processor.handle(tx);
+
+            check_error_kind(
+                &processor.accounts[&ClientId::from(1)],
+                ProcessingErrorKind::NegativeAmount,
+            );
+        }
+    }
+
+    #[test]
+    fn test_returns_error_on_withdrawal_with_with_insufficient_funds() {
+        let mut processor = TransactionProcessor::default();
+        let tx = withdraw(1.into(), 2.into(), Decimal::new(20, 1));
+
+        let _ = processor.handle(tx);
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::NotSufficientFunds,
+        );
+    }
+
+    #[test]
+    fn test_dispute_fails_when_transaction_already_disputed() {
+        let mut processor = TransactionProcessor::default();
+        for tx in [
+            deposit(1.into(), 2.into(), Decimal::new(20, 1)),
+            dispute(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let _ = processor.handle(dispute(1.into(), 2.into()));
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::DisputeReferencesAlreadyDisputedTx,
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_against_opening_balance() {
+        let mut processor =
+            TransactionProcessor::default().with_opening_balances(HashMap::from([(
+                1.into(),
+                (Decimal::new(100, 1), Decimal::new(20, 1), false),
+            )]));
+
+        let _ = processor.handle(withdraw(1.into(), 1.into(), Decimal::new(40, 1)));
+
+        let summary = processor.summary().next().unwrap();
+
+        assert_eq!(summary.client, 1.into());
+        assert_eq!(summary.available, Decimal::new(60, 1));
+        assert_eq!(summary.held, Decimal::new(20, 1));
+        assert_eq!(summary.total, Decimal::new(80, 1));
+        assert!(!summary.locked);
+    }
+
+    #[test]
+    fn test_open_disputes_are_reported_per_transaction_type() {
+        let mut processor = TransactionProcessor::default().with_open_disputes_report(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(100, 1)),
+            withdraw(1.into(), 2.into(), Decimal::new(20, 1)),
+            deposit(1.into(), 3.into(), Decimal::new(30, 1)),
+            dispute(1.into(), 3.into()),
+            dispute(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let summary = processor.summary().next().unwrap();
+
+        assert_eq!(summary.open_deposit_disputes, Some(1));
+        assert_eq!(summary.open_withdrawal_disputes, Some(1));
+    }
+
+    #[test]
+    fn test_transactions_beyond_client_limit_are_rejected() {
+        let mut processor = TransactionProcessor::default().with_max_transactions_per_client(2);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 3.into(), Decimal::new(10, 1)),
+            deposit(1.into(), 4.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::ClientTransactionLimit,
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::new(20, 1)
+        );
+        assert!(processor.accounts[&ClientId::from(2)].errors.is_empty());
+    }
+
+    #[test]
+    fn test_withdrawal_cannot_use_held_funds_by_default() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(30, 1)),
+            dispute(1.into(), 1.into()),
+            withdraw(1.into(), 3.into(), Decimal::new(40, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::NotSufficientFunds,
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_can_use_held_funds_when_allowed() {
+        let mut processor = TransactionProcessor::default().with_withdrawal_from_held(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(30, 1)),
+            dispute(1.into(), 1.into()),
+            withdraw(1.into(), 3.into(), Decimal::new(40, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.errors.is_empty());
+        assert_eq!(account.available, Decimal::new(-10, 1));
+        assert_eq!(account.held, Decimal::new(50, 1));
+    }
+
+    #[test]
+    fn test_take_errors_drains_recorded_errors() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(-10, 1)),
+            withdraw(2.into(), 2.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let kinds: Vec<_> = processor
+            .take_errors()
+            .into_iter()
+            .map(|error| error.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            [
+                ProcessingErrorKind::NegativeAmount,
+                ProcessingErrorKind::NotSufficientFunds
+            ]
+        );
+        assert!(processor.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_dispute_cycling_past_threshold_is_reported() {
+        let mut processor = TransactionProcessor::default().with_max_dispute_cycles(2);
+
+        let _ = processor.handle(deposit(1.into(), 1.into(), Decimal::new(10, 1)));
+
+        for _ in 0..2 {
+            let _ = processor.handle(dispute(1.into(), 1.into()));
+            let _ = processor.handle(resolve(1.into(), 1.into()));
+        }
+
+        assert!(processor.accounts[&ClientId::from(1)].errors.is_empty());
+
+        let _ = processor.handle(dispute(1.into(), 1.into()));
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::ExcessiveDisputeCycling,
+        );
+    }
+
+    #[test]
+    fn test_grand_totals() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(100, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(50, 1)),
+            deposit(2.into(), 3.into(), Decimal::new(20, 1)),
+            dispute(2.into(), 3.into()),
+            deposit(3.into(), 4.into(), Decimal::new(30, 1)),
+            dispute(3.into(), 4.into()),
+            chargeback(3.into(), 4.into()),
+            withdraw(4.into(), 5.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(
+            processor.grand_totals(),
+            GrandTotals {
+                clients: 4,
+                available: Decimal::new(150, 1),
+                held: Decimal::new(20, 1),
+                total: Decimal::new(170, 1),
+                locked_accounts: 1,
+                anomalous: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_grand_totals_overflow() {
+        let processor = TransactionProcessor::default().with_opening_balances(HashMap::from([
+            (1.into(), (Decimal::MAX, Decimal::ZERO, false)),
+            (2.into(), (Decimal::ONE, Decimal::ZERO, false)),
+        ]));
+
+        let totals = processor.grand_totals();
+
+        assert!(totals.anomalous);
+        assert_eq!(totals.available, Decimal::MAX);
+        assert_eq!(totals.total, Decimal::MAX);
+        assert_eq!(totals.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_dispute_beyond_global_held_limit_is_rejected() {
+        let mut processor =
+            TransactionProcessor::default().with_max_total_held(Decimal::new(50, 1));
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(30, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(20, 1)),
+            deposit(3.into(), 3.into(), Decimal::new(10, 1)),
+            dispute(1.into(), 1.into()),
+            dispute(2.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor.accounts[&ClientId::from(2)].errors.is_empty());
+
+        let _ = processor.handle(dispute(3.into(), 3.into()));
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(3)],
+            ProcessingErrorKind::GlobalHeldLimitExceeded,
+        );
+        assert_eq!(processor.total_held, Decimal::new(50, 1));
+    }
+
+    #[test]
+    fn test_deposit_beyond_ceiling_is_rejected() {
+        let mut processor = TransactionProcessor::default()
+            .with_balance_ceiling(Decimal::new(100, 1), CeilingPolicy::Reject);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(80, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(50, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::BalanceCeilingExceeded);
+        assert_eq!(account.available, Decimal::new(80, 1));
+    }
+
+    #[test]
+    fn test_deposit_beyond_ceiling_is_capped() {
+        let mut processor = TransactionProcessor::default()
+            .with_balance_ceiling(Decimal::new(100, 1), CeilingPolicy::Cap);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(80, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(50, 1)),
+            dispute(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.errors.is_empty());
+        assert_eq!(account.available, Decimal::new(80, 1));
+        assert_eq!(account.held, Decimal::new(20, 1));
+    }
+
+    #[test]
+    fn test_client_limits_override_global_limits() {
+        let mut processor = TransactionProcessor::default()
+            .with_max_transactions_per_client(1)
+            .with_client_limits(
+                2.into(),
+                Limits {
+                    max_transactions_per_client: Some(3),
+                    ..Default::default()
+                },
+            );
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 3.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 4.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 5.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::ClientTransactionLimit,
+        );
+        assert!(processor.accounts[&ClientId::from(2)].errors.is_empty());
+
+        let _ = processor.handle(deposit(2.into(), 6.into(), Decimal::new(10, 1)));
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(2)],
+            ProcessingErrorKind::ClientTransactionLimit,
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_chargeback_refunds_client_by_default() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(100, 1)),
+            withdraw(1.into(), 2.into(), Decimal::new(40, 1)),
+            dispute(1.into(), 2.into()),
+            chargeback(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let summary = processor.summary().next().unwrap();
+
+        assert!(summary.locked);
+        assert_eq!(summary.available, Decimal::new(100, 1));
+        assert_eq!(summary.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_withdrawal_chargeback_keeps_client_liable() {
+        let mut processor = TransactionProcessor::default()
+            .with_withdrawal_chargeback(WithdrawalChargeback::ClientLiable);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(100, 1)),
+            withdraw(1.into(), 2.into(), Decimal::new(40, 1)),
+            dispute(1.into(), 2.into()),
+            chargeback(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let summary = processor.summary().next().unwrap();
+
+        assert!(summary.locked);
+        assert_eq!(summary.available, Decimal::new(60, 1));
+        assert_eq!(summary.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_summary_for_clients_keeps_requested_order() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(20, 1)),
+            deposit(3.into(), 3.into(), Decimal::new(30, 1)),
+            withdraw(4.into(), 4.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let clients: Vec<_> = processor
+            .summary_for_clients(&[3.into(), 9.into(), 4.into(), 1.into()])
+            .into_iter()
+            .map(|summary| summary.client)
+            .collect();
+
+        assert_eq!(
+            clients,
+            [ClientId::from(3), ClientId::from(4), ClientId::from(1)]
+        );
+    }
+
+    #[test]
+    fn test_memo_reflects_account_state() {
+        let mut processor = TransactionProcessor::default().with_memo(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(10, 1)),
+            dispute(2.into(), 2.into()),
+            chargeback(2.into(), 2.into()),
+            deposit(3.into(), 3.into(), Decimal::new(10, 1)),
+            dispute(3.into(), 3.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let memos: Vec<_> = processor
+            .summary_for_clients(&[1.into(), 2.into(), 3.into()])
+            .into_iter()
+            .map(|summary| summary.memo)
+            .collect();
+
+        assert_eq!(
+            memos,
+            [
+                Some(AccountMemo::Ok),
+                Some(AccountMemo::Locked),
+                Some(AccountMemo::HasOpenDisputes)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overflowing_total_flags_account_as_anomalous() {
+        let processor = TransactionProcessor::default()
+            .with_memo(true)
+            .with_opening_balances(HashMap::from([(
+                1.into(),
+                (Decimal::MAX, Decimal::MAX, false),
+            )]));
+
+        let summary = processor.summary().next().unwrap();
+
+        assert!(summary.anomalous);
+        assert_eq!(summary.total, Decimal::MAX);
+        assert_eq!(summary.memo, Some(AccountMemo::Anomalous));
+    }
+
+    #[test]
+    fn test_net_available_excludes_held_funds() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(100, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(30, 1)),
+            dispute(1.into(), 2.into()),
+            deposit(2.into(), 3.into(), Decimal::new(50, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(processor.net_available(), Some(Decimal::new(150, 1)));
+        assert_eq!(processor.grand_totals().total, Decimal::new(180, 1));
+
+        let per_account: Decimal = processor
+            .summary()
+            .map(|summary| summary.net_available())
+            .sum();
+
+        assert_eq!(per_account, Decimal::new(150, 1));
+    }
+
+    #[test]
+    fn test_net_available_overflow() {
+        let processor = TransactionProcessor::default().with_opening_balances(HashMap::from([
+            (1.into(), (Decimal::MAX, Decimal::ZERO, false)),
+            (2.into(), (Decimal::ONE, Decimal::ZERO, false)),
+        ]));
+
+        assert_eq!(processor.net_available(), None);
+    }
+
+    #[test]
+    fn test_error_does_not_block_later_transactions() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            withdraw(1.into(), 2.into(), Decimal::new(100, 1)),
+            deposit(1.into(), 3.into(), Decimal::new(20, 1)),
+            dispute(1.into(), 1.into()),
+            resolve(1.into(), 1.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::NotSufficientFunds);
+        assert_eq!(account.available, Decimal::new(70, 1));
+        assert_eq!(account.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_transient_errors_do_not_block_deposits() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(5, 0)),
+            withdraw(1.into(), 2.into(), Decimal::new(10, 0)),
+            deposit(1.into(), 3.into(), Decimal::new(-1, 0)),
+            deposit(1.into(), 4.into(), Decimal::new(5, 0)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert_eq!(account.errors.len(), 2);
+        assert!(!account.locked);
+        assert_eq!(account.available, Decimal::TEN);
+    }
+
+    #[test]
+    fn test_error_blocks_later_transactions_when_freezing_on_error() {
+        let mut processor = TransactionProcessor::default().with_freeze_on_error(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            withdraw(1.into(), 2.into(), Decimal::new(100, 1)),
+            deposit(1.into(), 3.into(), Decimal::new(20, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::new(50, 1)
+        );
+    }
+
+    #[test]
+    fn test_repeated_errors_are_collapsed() {
+        let mut processor = TransactionProcessor::default().with_collapsed_errors(true);
+
+        for tx in [
+            withdraw(1.into(), 1.into(), Decimal::new(10, 1)),
+            withdraw(2.into(), 2.into(), Decimal::new(10, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(10, 1)),
+            withdraw(1.into(), 4.into(), Decimal::new(10, 1)),
+            deposit(1.into(), 5.into(), Decimal::new(-10, 1)),
+            withdraw(1.into(), 6.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let errors: Vec<_> = processor
+            .take_errors()
+            .into_iter()
+            .map(|error| (error.client, error.kind.clone(), error.occurrences()))
+            .collect();
+
+        assert_eq!(
+            errors,
+            [
+                (
+                    ClientId::from(1),
+                    ProcessingErrorKind::NotSufficientFunds,
+                    3
+                ),
+                (
+                    ClientId::from(2),
+                    ProcessingErrorKind::NotSufficientFunds,
+                    1
+                ),
+                (ClientId::from(1), ProcessingErrorKind::NegativeAmount, 1),
+                (
+                    ClientId::from(1),
+                    ProcessingErrorKind::NotSufficientFunds,
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_locked() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(10, 1)),
+            dispute(2.into(), 2.into()),
+            chargeback(2.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(processor.is_locked(1.into()), Some(false));
+        assert_eq!(processor.is_locked(2.into()), Some(true));
+        assert_eq!(processor.is_locked(3.into()), None);
+    }
+
+    #[test]
+    fn test_restore_from_snapshot() {
+        let before = [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(30, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(15, 1)),
+            dispute(1.into(), 1.into()),
+            dispute(2.into(), 2.into()),
+        ];
+        let after = || {
+            [
+                resolve(1.into(), 1.into()),
+                chargeback(2.into(), 2.into()),
+                deposit(3.into(), 4.into(), Decimal::new(10, 1)),
+                dispute(1.into(), 3.into()),
+            ]
+        };
+
+        let mut uninterrupted = TransactionProcessor::default();
+        let mut checkpointed = TransactionProcessor::default();
+
+        for tx in before {
+            let _ = uninterrupted.handle(tx.clone());
+            let _ = checkpointed.handle(tx);
+        }
+
+        let json = serde_json::to_string(&checkpointed.snapshot()).unwrap();
+        let mut restored = TransactionProcessor::restore(serde_json::from_str(&json).unwrap());
+
+        for tx in after() {
+            let _ = uninterrupted.handle(tx.clone());
+            let _ = restored.handle(tx);
+        }
+
+        assert_eq!(restored.snapshot(), uninterrupted.snapshot());
+        assert_eq!(
+            restored.summary().collect::<Vec<_>>(),
+            uninterrupted.summary().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_restore_keeps_account_errors() {
+        let processor = || {
+            TransactionProcessor::default()
+                .with_errors_in_summary(true)
+                .with_freeze_on_error(true)
+                .with_min_account_age(3)
+        };
+        let before = [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(-10, 1)),
+            deposit(2.into(), 3.into(), Decimal::new(30, 1)),
+        ];
+        let after = || {
+            [
+                deposit(1.into(), 4.into(), Decimal::new(10, 1)),
+                withdraw(2.into(), 5.into(), Decimal::new(10, 1)),
+                withdraw(2.into(), 6.into(), Decimal::new(10, 1)),
+            ]
+        };
+
+        let mut uninterrupted = processor();
+        let mut checkpointed = processor();
+
+        for tx in before {
+            let _ = uninterrupted.handle(tx.clone());
+            let _ = checkpointed.handle(tx);
+        }
+
+        let json = serde_json::to_string(&checkpointed.snapshot()).unwrap();
+        let mut restored = processor().with_snapshot(serde_json::from_str(&json).unwrap());
+
+        assert!(restored.had_errors());
+
+        for tx in after() {
+            let _ = uninterrupted.handle(tx.clone());
+            let _ = restored.handle(tx);
+        }
+
+        let account = &restored.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::NegativeAmount);
+        // frozen by its error, the later deposit was skipped
+        assert_eq!(account.available, Decimal::new(50, 1));
+        check_error_kind(
+            &restored.accounts[&ClientId::from(2)],
+            ProcessingErrorKind::AccountTooNew,
+        );
+        assert_eq!(restored.had_errors(), uninterrupted.had_errors());
+        assert_eq!(restored.snapshot(), uninterrupted.snapshot());
+        assert_eq!(
+            restored.summary().collect::<Vec<_>>(),
+            uninterrupted.summary().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_account_query() {
+        let mut processor = TransactionProcessor::default();
+
+        let _ = processor.handle(deposit(1.into(), 1.into(), Decimal::new(30, 1)));
+
+        let account = processor.account(1.into()).unwrap();
+        assert_eq!(account.available, Decimal::new(30, 1));
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total(), Decimal::new(30, 1));
+        assert!(!account.locked);
+
+        let _ = processor.handle(withdraw(1.into(), 2.into(), Decimal::new(12, 1)));
+
+        let account = processor.account(1.into()).unwrap();
+        assert_eq!(account.available, Decimal::new(18, 1));
+        assert_eq!(account.total(), Decimal::new(18, 1));
+
+        assert_eq!(processor.account(2.into()), None);
+    }
+
+    #[test]
+    fn test_summaries_keep_processor() {
+        let mut processor = TransactionProcessor::default();
+
+        let _ = processor.handle(deposit(1.into(), 1.into(), Decimal::new(20, 1)));
+
+        let first: Vec<_> = processor.summaries().collect();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].available, Decimal::new(20, 1));
+
+        let _ = processor.handle(withdraw(1.into(), 2.into(), Decimal::new(5, 1)));
+        let _ = processor.handle(deposit(2.into(), 3.into(), Decimal::new(10, 1)));
+
+        let second: Vec<_> = processor.summaries().collect();
+        assert_eq!(second.len(), 2);
+        assert_eq!(second[0].available, Decimal::new(15, 1));
+        assert_eq!(second[1].client, 2.into());
+        assert_eq!(second[1].total, Decimal::new(10, 1));
+    }
+
+    #[test]
+    fn test_transaction_counts_report() {
+        let txs = || {
+            [
+                deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+                deposit(1.into(), 2.into(), Decimal::new(10, 1)),
+                withdraw(1.into(), 3.into(), Decimal::new(20, 1)),
+                dispute(1.into(), 2.into()),
+                resolve(1.into(), 2.into()),
+                deposit(2.into(), 4.into(), Decimal::new(10, 1)),
+            ]
+        };
+
+        let mut processor = TransactionProcessor::default().with_transaction_counts_report(true);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let summaries: Vec<_> = processor.summary().collect();
+
+        assert_eq!(summaries[0].deposit_count, Some(2));
+        assert_eq!(summaries[0].withdrawal_count, Some(1));
+        assert_eq!(summaries[1].deposit_count, Some(1));
+        assert_eq!(summaries[1].withdrawal_count, Some(0));
+
+        let mut processor = TransactionProcessor::default();
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert_eq!(account.deposit_count, 2);
+        assert_eq!(account.withdrawal_count, 1);
+        assert!(processor
+            .summary()
+            .all(|summary| summary.deposit_count.is_none() && summary.withdrawal_count.is_none()));
+    }
+
+    #[test]
+    fn test_dangling_dispute_opens_no_account() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10, 1)),
+            dispute(2.into(), 7.into()),
+            resolve(3.into(), 8.into()),
+            chargeback(4.into(), 9.into()),
+        ] {
+            assert!(processor.handle(tx).is_ok());
+        }
+
+        let clients: Vec<_> = processor.summary().map(|summary| summary.client).collect();
+
+        assert_eq!(clients, [ClientId::from(1)]);
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            transfer(1.into(), 2.into(), 2.into(), Decimal::new(20, 1)),
+            dispute(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let sender = &processor.accounts[&ClientId::from(1)];
+        let recipient = &processor.accounts[&ClientId::from(2)];
+
+        assert!(sender.errors.is_empty());
+        assert_eq!(sender.available, Decimal::new(30, 1));
+        assert_eq!(sender.held, Decimal::ZERO);
+        assert_eq!(recipient.available, Decimal::new(20, 1));
+    }
+
+    #[test]
+    fn test_transfer_insufficient_funds() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(10, 1)),
+            transfer(1.into(), 3.into(), 2.into(), Decimal::new(20, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let sender = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(sender, ProcessingErrorKind::NotSufficientFunds);
+        assert_eq!(sender.available, Decimal::new(10, 1));
+        assert_eq!(
+            processor.accounts[&ClientId::from(2)].available,
+            Decimal::new(10, 1)
+        );
+
+        let _ = processor.handle(deposit(3.into(), 4.into(), Decimal::new(10, 1)));
+        let _ = processor.handle(dispute(3.into(), 4.into()));
+        let _ = processor.handle(chargeback(3.into(), 4.into()));
+        let result = processor.handle(transfer(2.into(), 5.into(), 3.into(), Decimal::ONE));
+
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ProcessingErrorKind::TransferRecipientUnavailable
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(2)].available,
+            Decimal::ONE
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(3)].available,
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn test_transfer_id_is_reserved() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            transfer(1.into(), 2.into(), 2.into(), Decimal::new(10, 1)),
+            transfer(1.into(), 2.into(), 3.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let errors: Vec<_> = processor
+            .errors
+            .iter()
+            .map(|error| (error.client, error.kind.clone()))
+            .collect();
+
+        assert_eq!(
+            errors,
+            [
+                (1.into(), ProcessingErrorKind::DuplicateTransactionId),
+                (2.into(), ProcessingErrorKind::DuplicateTransactionId),
+            ]
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::new(40, 1)
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(2)].available,
+            Decimal::new(10, 1)
+        );
+        assert!(!processor.accounts.contains_key(&ClientId::from(3)));
+    }
+
+    #[test]
+    fn test_transfer_credits_like_deposit() {
+        let crossings = Rc::new(RefCell::new(Vec::new()));
+
+        let mut processor = TransactionProcessor::default().with_threshold_hook(Decimal::TEN, {
+            let crossings = Rc::clone(&crossings);
+            move |client, total| crossings.borrow_mut().push((client, total))
+        });
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(20, 0)),
+            deposit(2.into(), 2.into(), Decimal::new(5, 0)),
+            transfer(1.into(), 3.into(), 2.into(), Decimal::new(6, 0)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let recipient = &processor.accounts[&ClientId::from(2)];
+
+        assert_eq!(recipient.available, Decimal::new(11, 0));
+        assert_eq!(recipient.total_deposited, Decimal::new(11, 0));
+        assert_eq!(recipient.deposit_count, 2);
+        assert_eq!(recipient.transaction_count, 2);
+        assert_eq!(
+            *crossings.borrow(),
+            [
+                (ClientId::from(1), Decimal::new(20, 0)),
+                (ClientId::from(2), Decimal::new(11, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transfer_respects_recipient_limits() {
+        let mut processor = TransactionProcessor::default()
+            .with_client_limits(
+                2.into(),
+                Limits {
+                    max_total: Some(Decimal::TEN),
+                    ..Default::default()
+                },
+            )
+            .with_client_limits(
+                3.into(),
+                Limits {
+                    max_transactions_per_client: Some(1),
+                    ..Default::default()
+                },
+            );
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(20, 0)),
+            deposit(2.into(), 2.into(), Decimal::new(5, 0)),
+            deposit(3.into(), 3.into(), Decimal::new(5, 0)),
+            transfer(1.into(), 4.into(), 2.into(), Decimal::new(6, 0)),
+            transfer(1.into(), 5.into(), 3.into(), Decimal::new(1, 0)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let errors: Vec<_> = processor
+            .errors
+            .iter()
+            .map(|error| (error.tx, error.kind.clone()))
+            .collect();
+
+        assert_eq!(
+            errors,
+            [
+                (4.into(), ProcessingErrorKind::BalanceCeilingExceeded),
+                (5.into(), ProcessingErrorKind::ClientTransactionLimit),
+            ]
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::new(20, 0)
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(2)].available,
+            Decimal::new(5, 0)
+        );
+        assert_eq!(
+            processor.accounts[&ClientId::from(3)].available,
+            Decimal::new(5, 0)
+        );
+    }
+
+    #[test]
+    fn test_transfer_counts_as_funding() {
+        let mut processor = TransactionProcessor::default()
+            .with_dispute_before_funds_rejection(true)
+            .with_max_dispute_to_deposits(Decimal::ONE);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            transfer(1.into(), 2.into(), 2.into(), Decimal::new(50, 1)),
+            withdraw(2.into(), 3.into(), Decimal::new(50, 1)),
+            dispute(2.into(), 3.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let recipient = &processor.accounts[&ClientId::from(2)];
+
+        assert!(recipient.errors.is_empty());
+        assert_eq!(recipient.held, Decimal::new(50, 1));
+    }
+
+    #[test]
+    fn test_self_transfer_is_rejected() {
+        let mut processor = TransactionProcessor::default().with_zero_account_closing(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            transfer(1.into(), 2.into(), 1.into(), Decimal::new(50, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::SelfTransfer);
+        assert_eq!(account.available, Decimal::new(50, 1));
+        assert_eq!(account.deposit_count, 1);
+    }
+
+    #[test]
+    fn test_transfer_from_new_account_is_rejected() {
+        let mut processor = TransactionProcessor::default().with_min_account_age(3);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::TEN),
+            transfer(1.into(), 2.into(), 2.into(), Decimal::ONE),
+            deposit(3.into(), 3.into(), Decimal::ONE),
+            transfer(1.into(), 4.into(), 2.into(), Decimal::ONE),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let sender = &processor.accounts[&ClientId::from(1)];
+        let failed: Vec<_> = sender.errors.iter().map(|error| error.tx).collect();
+
+        check_error_kind(sender, ProcessingErrorKind::AccountTooNew);
+        assert_eq!(failed, [TransactionId::from(2)]);
+        assert_eq!(sender.available, Decimal::new(9, 0));
+        assert_eq!(
+            processor.accounts[&ClientId::from(2)].available,
+            Decimal::ONE
+        );
+    }
+
+    #[test]
+    fn test_freeze_and_unfreeze() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            Transaction::Freeze(Freeze {
+                client: 1.into(),
+                transaction_id: 2.into(),
+            }),
+            deposit(1.into(), 3.into(), Decimal::new(10, 1)),
+            withdraw(1.into(), 4.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.locked);
+        assert!(account.errors.is_empty());
+        assert_eq!(account.available, Decimal::new(50, 1));
+
+        for tx in [
+            Transaction::Unfreeze(Unfreeze {
+                client: 1.into(),
+                transaction_id: 5.into(),
+            }),
+            withdraw(1.into(), 6.into(), Decimal::new(10, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(!account.locked);
+        assert_eq!(account.available, Decimal::new(40, 1));
+    }
+
+    #[test]
+    fn test_freeze_not_counted_toward_transaction_limit() {
+        let mut processor = TransactionProcessor::default().with_max_transactions_per_client(1);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            Transaction::Freeze(Freeze {
+                client: 1.into(),
+                transaction_id: 2.into(),
+            }),
+            Transaction::Unfreeze(Unfreeze {
+                client: 1.into(),
+                transaction_id: 3.into(),
+            }),
+            Transaction::Freeze(Freeze {
+                client: 1.into(),
+                transaction_id: 4.into(),
+            }),
+        ] {
+            assert!(processor.handle(tx).is_ok());
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.locked);
+        assert!(account.errors.is_empty());
+        assert_eq!(account.transaction_count, 1);
+    }
+
+    #[test]
+    fn test_resolving_last_dispute_unlocks_account() {
+        let mut processor = TransactionProcessor::default().with_auto_unlock_on_last_resolve(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(30, 1)),
+            dispute(1.into(), 1.into()),
+            dispute(1.into(), 2.into()),
+            chargeback(1.into(), 1.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(processor.is_locked(1.into()), Some(true));
+
+        let _ = processor.handle(resolve(1.into(), 2.into()));
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(!account.locked);
+        assert_eq!(account.available, Decimal::new(30, 1));
+        assert_eq!(account.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_resolve_on_locked_account_is_skipped_by_default() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(30, 1)),
+            dispute(1.into(), 1.into()),
+            dispute(1.into(), 2.into()),
+            chargeback(1.into(), 1.into()),
+            resolve(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.locked);
+        assert_eq!(account.held, Decimal::new(30, 1));
+    }
+
+    #[test]
+    fn test_error_kind_serializes_to_code_and_message() {
+        assert_eq!(
+            serde_json::to_value(ProcessingErrorKind::NegativeAmount).unwrap(),
+            serde_json::json!({
+                "code": "NEGATIVE_AMOUNT",
+                "message": "Cannot execute transactions with negative amount",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(ProcessingErrorKind::ChargebackWhenTxNotUnderDispute).unwrap(),
+            serde_json::json!({
+                "code": "CHARGEBACK_WHEN_TX_NOT_UNDER_DISPUTE",
+                "message": "Cannot chargeback transaction when not under dispute",
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_kind_deserializes_from_code() {
+        for kind in ProcessingErrorKind::ALL {
+            let json = serde_json::to_string(&kind).unwrap();
+
+            assert_eq!(
+                serde_json::from_str::<ProcessingErrorKind>(&json).unwrap(),
+                kind
+            );
+        }
+
+        assert!(serde_json::from_str::<ProcessingErrorKind>(r#"{"code":"UNKNOWN"}"#).is_err());
+    }
+
+    #[test]
+    fn test_type_priority_applies_dispute_preceding_deposit() {
+        let batch = vec![
+            dispute(1.into(), 1.into()),
+            deposit(1.into(), 1.into(), Decimal::new(20, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(10, 1)),
+        ];
+
+        let mut unsorted = TransactionProcessor::default();
+
+        for tx in batch.clone() {
+            let _ = unsorted.handle(tx);
+        }
+
+        assert_eq!(unsorted.accounts[&ClientId::from(1)].held, Decimal::ZERO);
+
+        let mut sorted_batch = batch;
+        sort_by_type_priority(&mut sorted_batch);
+
+        assert_eq!(
+            sorted_batch
+                .iter()
+                .map(Transaction::type_name)
+                .collect::<Vec<_>>(),
+            ["deposit", "deposit", "dispute"]
+        );
+
+        let mut sorted = TransactionProcessor::default();
+
+        for tx in sorted_batch {
+            let _ = sorted.handle(tx);
+        }
+
+        let account = &sorted.accounts[&ClientId::from(1)];
+
+        assert_eq!(account.available, Decimal::new(10, 1));
+        assert_eq!(account.held, Decimal::new(20, 1));
+    }
+
+    #[test]
+    fn test_dispute_state_round_trip() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(30, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(10, 1)),
+            deposit(2.into(), 4.into(), Decimal::new(20, 1)),
+            dispute(1.into(), 1.into()),
+            dispute(1.into(), 3.into()),
+            dispute(2.into(), 4.into()),
+            resolve(2.into(), 4.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let mut exported = processor.disputes();
+        exported.sort_by_key(|dispute| dispute.amount);
+
+        assert_eq!(
+            exported,
+            [
+                DisputedTransaction {
+                    client: 1.into(),
+                    tx: 3.into(),
+                    amount: Decimal::new(10, 1),
+                    is_deposit: false,
+                },
+                DisputedTransaction {
+                    client: 1.into(),
+                    tx: 1.into(),
+                    amount: Decimal::new(50, 1),
+                    is_deposit: true,
+                },
+            ]
+        );
+
+        // the disputed funds are carried over as held, as the chargeback below releases them
+        let mut imported = TransactionProcessor::default()
+            .with_opening_balances(HashMap::from([(
+                1.into(),
+                (Decimal::ZERO, Decimal::new(60, 1), false),
+            )]))
+            .with_disputes(exported.clone());
+
+        let mut reexported = imported.disputes();
+        reexported.sort_by_key(|dispute| dispute.amount);
+
+        assert_eq!(reexported, exported);
+
+        let account = &imported.accounts[&ClientId::from(1)];
+
+        assert_eq!(account.open_deposit_disputes, 1);
+        assert_eq!(account.open_withdrawal_disputes, 1);
+        assert_eq!(account.held, Decimal::new(60, 1));
+
+        let _ = imported.handle(chargeback(1.into(), 1.into()));
+
+        assert_eq!(imported.is_locked(1.into()), Some(true));
+        assert_eq!(imported.disputes().len(), 1);
+    }
+
+    #[test]
+    fn test_resolved_withdrawal_dispute_credits_nothing() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(100, 0)),
+            withdraw(1.into(), 2.into(), Decimal::new(40, 0)),
+            dispute(1.into(), 2.into()),
+            resolve(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.errors.is_empty());
+        assert_eq!(account.available, Decimal::new(60, 0));
+        assert_eq!(account.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_handle_batch_reports_each_item() {
+        let mut processor = TransactionProcessor::default();
+
+        let results = processor.handle_batch(vec![
+            deposit(1.into(), 1.into(), Decimal::ONE),
+            withdraw(1.into(), 2.into(), Decimal::TWO),
+            deposit(1.into(), 3.into(), Decimal::new(-1, 0)),
+            withdraw(1.into(), 4.into(), Decimal::ONE),
+        ]);
+
+        let kinds: Vec<_> = results
+            .iter()
+            .map(|result| result.as_ref().err().map(ProcessingError::kind))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            [
+                None,
+                Some(&ProcessingErrorKind::NotSufficientFunds),
+                Some(&ProcessingErrorKind::NegativeAmount),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reference_to_other_clients_transaction() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::TEN),
+            deposit(2.into(), 2.into(), Decimal::ONE),
+            dispute(2.into(), 1.into()),
+            dispute(1.into(), 1.into()),
+            resolve(2.into(), 1.into()),
+            chargeback(2.into(), 1.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let owner = &processor.accounts[&ClientId::from(1)];
+        let other = &processor.accounts[&ClientId::from(2)];
+
+        assert!(owner.errors.is_empty());
+        assert_eq!(owner.held, Decimal::TEN);
+        assert!(!owner.locked);
+        assert_eq!(other.errors.len(), 3);
+        check_error_kind(other, ProcessingErrorKind::ClientMismatch);
+        assert_eq!(other.available, Decimal::ONE);
+    }
+
+    #[test]
+    fn test_withdrawal_disputes_disabled() {
+        let txs = || {
+            [
+                deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+                withdraw(1.into(), 2.into(), Decimal::new(20, 1)),
+                dispute(1.into(), 2.into()),
+            ]
+        };
+
+        let mut processor = TransactionProcessor::default().with_withdrawal_disputes_allowed(false);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::WithdrawalDisputesDisabled);
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.open_withdrawal_disputes, 0);
+
+        assert!(
+            TransactionProcessor::default()
+                .config()
+                .allow_withdrawal_disputes
+        );
+
+        let mut processor = TransactionProcessor::default();
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.errors.is_empty());
+        assert_eq!(account.held, Decimal::new(20, 1));
+        assert_eq!(account.open_withdrawal_disputes, 1);
+    }
+
+    #[test]
+    fn test_dispute_limit_per_tx() {
+        let txs = || {
+            [
+                deposit(1.into(), 1.into(), Decimal::new(5, 0)),
+                dispute(1.into(), 1.into()),
+                resolve(1.into(), 1.into()),
+                dispute(1.into(), 1.into()),
+            ]
+        };
+
+        let mut processor = TransactionProcessor::default().with_max_disputes_per_tx(1);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::DisputeLimitExceeded);
+        assert_eq!(account.available, Decimal::new(5, 0));
+        assert_eq!(account.held, Decimal::ZERO);
+
+        let mut processor = TransactionProcessor::default().with_max_disputes_per_tx(2);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.errors.is_empty());
+        assert_eq!(account.available, Decimal::ZERO);
+        assert_eq!(account.held, Decimal::new(5, 0));
+
+        let mut processor = TransactionProcessor::default();
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor.accounts[&ClientId::from(1)].errors.is_empty());
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_before_any_funds() {
+        let txs = || {
+            [
+                withdraw(1.into(), 1.into(), Decimal::ZERO),
+                dispute(1.into(), 1.into()),
+            ]
+        };
+
+        let mut processor =
+            TransactionProcessor::default().with_dispute_before_funds_rejection(true);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::DisputeBeforeAnyFunds);
+        assert_eq!(account.open_withdrawal_disputes, 0);
+
+        let mut processor = TransactionProcessor::default();
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.errors.is_empty());
+        assert_eq!(account.open_withdrawal_disputes, 1);
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_after_closing_is_funded() {
+        let mut processor = TransactionProcessor::default()
+            .with_zero_account_closing(true)
+            .with_dispute_before_funds_rejection(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(5, 0)),
+            withdraw(1.into(), 2.into(), Decimal::new(5, 0)),
+            dispute(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.errors.is_empty());
+        assert_eq!(account.held, Decimal::new(5, 0));
+    }
+
+    #[test]
+    fn test_summary_ordered_by_client() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(3.into(), 1.into(), Decimal::ONE),
+            deposit(1.into(), 2.into(), Decimal::ONE),
+            deposit(2.into(), 3.into(), Decimal::ONE),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let clients: Vec<_> = processor.summary().map(|summary| summary.client).collect();
+
+        assert_eq!(clients, [1.into(), 2.into(), 3.into()]);
+    }
+
+    #[test]
+    fn test_withdrawal_never_uses_held_funds() {
+        let configurations = [
+            TransactionProcessor::default(),
+            TransactionProcessor::default().with_sandbox(true),
+            TransactionProcessor::default().with_auto_unlock_on_last_resolve(true),
+            TransactionProcessor::default().with_dispute_hold_scale(2),
+            TransactionProcessor::default().with_normalized_amounts(true),
+            TransactionProcessor::default().with_withdrawal_holds_excluded_from_total(true),
+            TransactionProcessor::default().with_scale(4),
+        ];
+
+        for mut processor in configurations {
+            for tx in [
+                deposit(1.into(), 1.into(), Decimal::TEN),
+                deposit(1.into(), 2.into(), Decimal::ONE),
+                dispute(1.into(), 1.into()),
+            ] {
+                let _ = processor.handle(tx);
+            }
+
+            let error = processor
+                .handle(withdraw(1.into(), 3.into(), Decimal::TWO))
+                .unwrap_err();
+
+            let account = &processor.accounts[&ClientId::from(1)];
+
+            assert_eq!(error.kind(), &ProcessingErrorKind::NotSufficientFunds);
+            assert_eq!(account.available, Decimal::ONE);
+            assert_eq!(account.held, Decimal::TEN);
+        }
+
+        let mut processor = TransactionProcessor::default().with_withdrawal_from_held(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::TEN),
+            deposit(1.into(), 2.into(), Decimal::ONE),
+            dispute(1.into(), 1.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor
+            .handle(withdraw(1.into(), 3.into(), Decimal::TWO))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_summary_rounds_to_output_scale() {
+        let third = Decimal::ONE / Decimal::from(3);
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), third),
+            deposit(1.into(), 2.into(), third),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            third + third
+        );
+
+        let mut output = Vec::new();
+        crate::output::write_summaries(
+            &mut output,
+            &crate::output::OutputOptions::default(),
+            processor.summary(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n1,0.6667,0.0,0.6667,false\n"
+        );
+    }
+
+    #[test]
+    fn test_scale_limit() {
+        let mut processor = TransactionProcessor::default().with_scale(4);
+
+        assert!(processor
+            .handle(deposit(1.into(), 1.into(), Decimal::new(12345, 4)))
+            .is_ok());
+        assert!(processor
+            .handle(withdraw(1.into(), 2.into(), Decimal::new(1, 4)))
+            .is_ok());
+
+        for tx in [
+            deposit(1.into(), 3.into(), Decimal::new(123456, 5)),
+            deposit(1.into(), 4.into(), Decimal::new(123450, 5)),
+            withdraw(1.into(), 5.into(), Decimal::new(10000, 5)),
+        ] {
+            let error = processor.handle(tx).unwrap_err();
+
+            assert_eq!(error.kind(), &ProcessingErrorKind::TooManyDecimalPlaces);
+        }
+
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::new(12344, 4)
+        );
+
+        let mut cents = TransactionProcessor::default().with_scale(2);
+
+        assert_eq!(
+            cents
+                .handle(deposit(1.into(), 1.into(), Decimal::new(12300, 4)))
+                .unwrap_err()
+                .kind(),
+            &ProcessingErrorKind::TooManyDecimalPlaces
+        );
+
+        let mut lenient = TransactionProcessor::default();
+
+        assert!(lenient
+            .handle(deposit(1.into(), 1.into(), Decimal::new(123456, 5)))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_amount_overflow_is_rejected() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::MAX),
+            deposit(1.into(), 2.into(), Decimal::MAX),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::AmountOverflow);
+        assert_eq!(account.available, Decimal::MAX);
+        assert_eq!(account.total_deposited, Decimal::MAX);
+    }
+
+    #[test]
+    fn test_duplicate_transaction_id() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 7.into(), Decimal::TEN),
+            deposit(1.into(), 7.into(), Decimal::ONE),
+            withdraw(2.into(), 7.into(), Decimal::ZERO),
+            dispute(1.into(), 7.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::DuplicateTransactionId);
+        assert_eq!(account.errors.len(), 1);
+        assert_eq!(account.held, Decimal::TEN);
+        assert_eq!(account.available, Decimal::ZERO);
+        check_error_kind(
+            &processor.accounts[&ClientId::from(2)],
+            ProcessingErrorKind::DuplicateTransactionId,
+        );
+    }
+
+    #[test]
+    fn test_min_account_age() {
+        let mut processor = TransactionProcessor::default().with_min_account_age(3);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::TEN),
+            withdraw(1.into(), 2.into(), Decimal::ONE),
+            deposit(2.into(), 3.into(), Decimal::ONE),
+            withdraw(1.into(), 4.into(), Decimal::ONE),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+        let failed: Vec<_> = account.errors.iter().map(|error| error.tx).collect();
+
+        check_error_kind(account, ProcessingErrorKind::AccountTooNew);
+        assert_eq!(failed, [TransactionId::from(2)]);
+        assert_eq!(account.available, Decimal::new(9, 0));
+
+        let mut processor = TransactionProcessor::default()
+            .with_opening_balances(HashMap::from([(
+                ClientId::from(1),
+                (Decimal::TEN, Decimal::ZERO, false),
+            )]))
+            .with_min_account_age(3);
+
+        assert!(processor
+            .handle(withdraw(1.into(), 1.into(), Decimal::ONE))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_dispute_hold_rounding_conserves_funds() {
+        let txs = |settlement| {
+            [
+                deposit(1.into(), 1.into(), Decimal::TEN),
+                deposit(1.into(), 2.into(), Decimal::new(123456, 5)),
+                dispute(1.into(), 2.into()),
+                settlement,
+            ]
+        };
+
+        let mut disputed = TransactionProcessor::default().with_dispute_hold_scale(2);
+
+        for tx in txs(deposit(2.into(), 3.into(), Decimal::ONE)) {
+            let _ = disputed.handle(tx);
+        }
+
+        let account = &disputed.accounts[&ClientId::from(1)];
+
+        assert_eq!(account.held, Decimal::new(123, 2));
+        assert_eq!(account.available, Decimal::new(1000456, 5));
+        assert_eq!(disputed.total_held, Decimal::new(123, 2));
+
+        let mut resolved = TransactionProcessor::default().with_dispute_hold_scale(2);
+
+        for tx in txs(resolve(1.into(), 2.into())) {
+            let _ = resolved.handle(tx);
+        }
+
+        let account = &resolved.accounts[&ClientId::from(1)];
+
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.available, Decimal::new(1123456, 5));
+
+        let mut charged_back = TransactionProcessor::default().with_dispute_hold_scale(2);
+
+        for tx in txs(chargeback(1.into(), 2.into())) {
+            let _ = charged_back.handle(tx);
+        }
+
+        let account = &charged_back.accounts[&ClientId::from(1)];
+
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.available, Decimal::TEN);
+        assert_eq!(charged_back.total_held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_withdrawal_holds_excluded_from_total() {
+        let txs = || {
+            [
+                deposit(1.into(), 1.into(), Decimal::TEN),
+                withdraw(1.into(), 2.into(), Decimal::new(4, 0)),
+                dispute(1.into(), 2.into()),
+            ]
+        };
+
+        let mut included = TransactionProcessor::default();
+        let mut excluded =
+            TransactionProcessor::default().with_withdrawal_holds_excluded_from_total(true);
+
+        for tx in txs() {
+            let _ = included.handle(tx);
+        }
+
+        for tx in txs() {
+            let _ = excluded.handle(tx);
+        }
+
+        let included = included.summary().next().unwrap();
+        let excluded = excluded.summary().next().unwrap();
+
+        assert_eq!(included.held, Decimal::new(4, 0));
+        assert_eq!(included.total, Decimal::TEN);
+        assert_eq!(excluded.held, Decimal::new(4, 0));
+        assert_eq!(excluded.total, Decimal::new(6, 0));
+    }
+
+    #[test]
+    fn test_rounded_withdrawal_holds_excluded_from_total() {
+        let mut processor = TransactionProcessor::default()
+            .with_dispute_hold_scale(2)
+            .with_withdrawal_holds_excluded_from_total(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::TEN),
+            withdraw(1.into(), 2.into(), Decimal::new(12345, 4)),
+            dispute(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let summary = processor.summary().next().unwrap();
+
+        // exactly the rounded hold is left out, not the withdrawn amount
+        assert_eq!(summary.held, Decimal::new(123, 2));
+        assert_eq!(summary.available, Decimal::new(87655, 4));
+        assert_eq!(summary.total, Decimal::new(87655, 4));
+    }
+
+    #[test]
+    fn test_lock_policy_spares_small_chargebacks() {
+        let mut processor =
+            TransactionProcessor::default().with_lock_policy(|_, _, amount| amount >= Decimal::TEN);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::ONE),
+            dispute(1.into(), 1.into()),
+            chargeback(1.into(), 1.into()),
+            deposit(2.into(), 2.into(), Decimal::new(20, 0)),
+            dispute(2.into(), 2.into()),
+            chargeback(2.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let small = &processor.accounts[&ClientId::from(1)];
+        let large = &processor.accounts[&ClientId::from(2)];
+
+        assert!(!small.locked);
+        assert_eq!(small.available + small.held, Decimal::ZERO);
+        assert!(large.locked);
+        assert!(!processor.had_errors());
+    }
+
+    #[test]
+    fn test_threshold_hook_fires_on_crossing() {
+        let crossings = Rc::new(RefCell::new(Vec::new()));
+
+        let mut processor = TransactionProcessor::default().with_threshold_hook(Decimal::TEN, {
+            let crossings = Rc::clone(&crossings);
+            move |client, total| crossings.borrow_mut().push((client, total))
+        });
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(4, 0)),
+            deposit(1.into(), 2.into(), Decimal::new(4, 0)),
+            deposit(1.into(), 3.into(), Decimal::new(4, 0)),
+            deposit(1.into(), 4.into(), Decimal::new(1, 0)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(
+            *crossings.borrow(),
+            [(ClientId::from(1), Decimal::new(12, 0))]
+        );
+
+        let _ = processor.handle(withdraw(1.into(), 5.into(), Decimal::new(5, 0)));
+
+        assert_eq!(
+            *crossings.borrow(),
+            [
+                (ClientId::from(1), Decimal::new(12, 0)),
+                (ClientId::from(1), Decimal::new(8, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zero_value_resolve() {
+        let txs = || {
+            [
+                deposit(1.into(), 1.into(), Decimal::ZERO),
+                dispute(1.into(), 1.into()),
+                resolve(1.into(), 1.into()),
+            ]
+        };
+
+        let mut processor = TransactionProcessor::default();
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor.accounts[&ClientId::from(1)].errors.is_empty());
+        assert!(processor.disputes().is_empty());
+
+        let mut processor = TransactionProcessor::default().with_zero_value_resolve_rejection(true);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::ZeroValueResolve,
+        );
+        assert_eq!(processor.disputes().len(), 1);
+    }
+
+    #[test]
+    fn test_strict_clients() {
+        let opened = || HashMap::from([(ClientId::from(1), (Decimal::ZERO, Decimal::ZERO, false))]);
+
+        let mut processor = TransactionProcessor::default().with_opening_balances(opened());
+
+        let _ = processor.handle(deposit(2.into(), 1.into(), Decimal::ONE));
+
+        assert_eq!(
+            processor.accounts[&ClientId::from(2)].available,
+            Decimal::ONE
+        );
+
+        let mut processor = TransactionProcessor::default()
+            .with_opening_balances(opened())
+            .with_strict_clients(true);
+
+        let _ = processor.handle(deposit(1.into(), 1.into(), Decimal::ONE));
+        let _ = processor.handle(deposit(2.into(), 2.into(), Decimal::ONE));
+
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::ONE
+        );
+        assert!(!processor.accounts.contains_key(&ClientId::from(2)));
+
+        let errors = processor.take_errors();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ProcessingErrorKind::UnknownClient);
+        assert_eq!(errors[0].client, ClientId::from(2));
+    }
+
+    #[test]
+    fn test_config_reflects_builder_options() {
+        let processor = TransactionProcessor::default()
+            .with_max_transactions_per_client(10)
+            .with_client_limits(
+                2.into(),
+                Limits {
+                    max_dispute_cycles: Some(1),
+                    ..Default::default()
+                },
+            )
+            .with_balance_ceiling(Decimal::ONE_HUNDRED, CeilingPolicy::Cap)
+            .with_withdrawal_chargeback(WithdrawalChargeback::ClientLiable)
+            .with_strict_clients(true);
+
+        let config = processor.config();
+
+        assert_eq!(config.limits.max_transactions_per_client, Some(10));
+        assert_eq!(config.limits.max_total, Some(Decimal::ONE_HUNDRED));
+        assert_eq!(config.ceiling_policy, CeilingPolicy::Cap);
+        assert_eq!(
+            config.withdrawal_chargeback,
+            WithdrawalChargeback::ClientLiable
+        );
+        assert!(config.strict_clients);
+        assert!(!config.freeze_on_error);
+        assert_eq!(
+            config.limits_for(2.into()),
+            Limits {
+                max_transactions_per_client: Some(10),
+                max_dispute_cycles: Some(1),
+                max_open_disputes_per_account: None,
+                max_total: Some(Decimal::ONE_HUNDRED),
+            }
+        );
+    }
+
+    #[test]
+    fn test_normalized_amounts() {
+        let mut processor = TransactionProcessor::default().with_normalized_amounts(true);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(15000, 4)),
+            deposit(1.into(), 2.into(), Decimal::new(150, 2)),
+            withdraw(1.into(), 3.into(), Decimal::new(2000, 3)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert_eq!(
+            processor.transactions[&TransactionId::from(1)]
+                .amount
+                .scale(),
+            1
+        );
+        assert_eq!(
+            processor.transactions[&TransactionId::from(3)]
+                .amount
+                .scale(),
+            0
+        );
+
+        let summary = processor.summary().next().unwrap();
+
+        assert_eq!(summary.available, Decimal::ONE);
+        assert_eq!(summary.available.scale(), 0);
+        assert_eq!(summary.total.scale(), 0);
+    }
+
+    #[test]
+    fn test_consistency_scan() {
+        let mut processor = TransactionProcessor::default();
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(20, 1)),
+            withdraw(1.into(), 3.into(), Decimal::new(10, 1)),
+            dispute(1.into(), 1.into()),
+            dispute(1.into(), 3.into()),
+            resolve(1.into(), 3.into()),
+            deposit(2.into(), 4.into(), Decimal::new(30, 1)),
+            dispute(2.into(), 4.into()),
+            chargeback(2.into(), 4.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor.inconsistent_accounts().is_empty());
+
+        let processor = TransactionProcessor::default().with_opening_balances(HashMap::from([(
+            3.into(),
+            (Decimal::ZERO, Decimal::ONE, false),
+        )]));
+
+        assert_eq!(processor.inconsistent_accounts(), [ClientId::from(3)]);
+    }
+
+    #[test]
+    fn test_max_open_disputes_per_account() {
+        let mut processor = TransactionProcessor::default().with_max_open_disputes_per_account(2);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::ONE),
+            deposit(1.into(), 2.into(), Decimal::ONE),
+            deposit(1.into(), 3.into(), Decimal::ONE),
+            dispute(1.into(), 1.into()),
+            dispute(1.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        assert!(processor.accounts[&ClientId::from(1)].errors.is_empty());
+
+        let _ = processor.handle(dispute(1.into(), 3.into()));
+
+        let account = &processor.accounts[&ClientId::from(1)];
 
-        // we skip processing an account that has been locked or if a transaction resulted in an error
-        if account.locked || account.error.is_some() {
-            return;
-        }
+        check_error_kind(account, ProcessingErrorKind::TooManyOpenDisputes);
+        assert_eq!(account.held, Decimal::TWO);
+        assert_eq!(account.open_deposit_disputes, 2);
 
-        match &tx {
-            Transaction::Deposit(deposit) => {
-                if deposit.amount < Decimal::ZERO {
-                    account.error = Some(ProcessingError {
-                        client: deposit.client,
-                        tx: deposit.transaction_id,
-                        kind: ProcessingErrorKind::NegativeAmount,
-                    });
-
-                    return;
-                }
+        let _ = processor.handle(resolve(1.into(), 1.into()));
+        let _ = processor.handle(dispute(1.into(), 3.into()));
 
-                account.available += deposit.amount;
-            }
-            Transaction::Withdrawal(withdrawal) => {
-                if withdrawal.amount < Decimal::ZERO {
-                    account.error = Some(ProcessingError {
-                        client: withdrawal.client,
-                        tx: withdrawal.transaction_id,
-                        kind: ProcessingErrorKind::NegativeAmount,
-                    });
-
-                    return;
-                }
+        let account = &processor.accounts[&ClientId::from(1)];
 
-                if withdrawal.amount > account.available {
-                    account.error = Some(ProcessingError {
-                        client: withdrawal.client,
-                        tx: withdrawal.transaction_id,
-                        kind: ProcessingErrorKind::NotSufficientFunds,
-                    });
+        assert_eq!(account.held, Decimal::TWO);
+        assert_eq!(account.available, Decimal::ONE);
+    }
 
-                    return;
-                }
+    #[test]
+    fn test_excess_precision_per_transaction_type() {
+        let mut processor = TransactionProcessor::default().with_max_scale(
+            2,
+            ExcessPrecision::Round,
+            ExcessPrecision::Reject,
+        );
 
-                account.available -= withdrawal.amount;
-            }
-            Transaction::Dispute(dispute) => {
-                let Some(tx_state) = self.transactions.get_mut(&dispute.transaction_id) else {
-                    return;
-                };
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(10005, 3)),
+            deposit(2.into(), 2.into(), Decimal::new(1000, 2)),
+            withdraw(2.into(), 3.into(), Decimal::new(1001, 3)),
+            withdraw(2.into(), 4.into(), Decimal::new(10000, 4)),
+        ] {
+            let _ = processor.handle(tx);
+        }
 
-                if tx_state.is_under_dispute {
-                    account.error = Some(ProcessingError {
-                        client: dispute.client,
-                        tx: dispute.transaction_id,
-                        kind: ProcessingErrorKind::DisputeReferencesAlreadyDisputedTx,
-                    });
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::new(1000, 2)
+        );
+        assert!(processor.accounts[&ClientId::from(1)].errors.is_empty());
 
-                    return;
-                }
+        let account = &processor.accounts[&ClientId::from(2)];
 
-                if tx_state.is_deposit {
-                    if tx_state.amount > account.available {
-                        account.error = Some(ProcessingError {
-                            client: dispute.client,
-                            tx: dispute.transaction_id,
-                            kind: ProcessingErrorKind::NotSufficientFundsForDispute,
-                        });
-
-                        return;
-                    }
+        check_error_kind(account, ProcessingErrorKind::ExcessPrecision);
+        assert_eq!(account.available, Decimal::new(900, 2));
+    }
 
-                    account.available -= tx_state.amount;
-                    account.held += tx_state.amount;
-                } else {
-                    account.held += tx_state.amount;
-                }
+    #[test]
+    fn test_clear_account() {
+        let mut processor = TransactionProcessor::default();
 
-                tx_state.is_under_dispute = true;
-            }
-            Transaction::Resolve(resolve) => {
-                let Some(tx_state) = self.transactions.get_mut(&resolve.transaction_id) else {
-                    return;
-                };
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(2.into(), 2.into(), Decimal::new(30, 1)),
+            dispute(2.into(), 2.into()),
+        ] {
+            let _ = processor.handle(tx);
+        }
 
-                if !tx_state.is_under_dispute {
-                    account.error = Some(ProcessingError {
-                        client: resolve.client,
-                        tx: resolve.transaction_id,
-                        kind: ProcessingErrorKind::ResolveWhenTxNotUnderDispute,
-                    });
+        assert!(processor.clear_account(2.into()));
+        assert!(!processor.clear_account(2.into()));
+        assert_eq!(processor.total_held, Decimal::ZERO);
 
-                    return;
-                }
+        let _ = processor.handle(deposit(2.into(), 3.into(), Decimal::ONE));
+        let _ = processor.handle(resolve(2.into(), 2.into()));
+        let _ = processor.handle(dispute(2.into(), 2.into()));
 
-                account.available += tx_state.amount;
-                account.held -= tx_state.amount;
+        let account = &processor.accounts[&ClientId::from(2)];
 
-                tx_state.is_under_dispute = false;
-            }
-            Transaction::Chargeback(chargeback) => {
-                let Some(tx_state) = self.transactions.get_mut(&chargeback.transaction_id) else {
-                    return;
-                };
+        assert_eq!(account.available, Decimal::ONE);
+        assert_eq!(account.held, Decimal::ZERO);
+        assert!(account.errors.is_empty());
 
-                if !tx_state.is_under_dispute {
-                    account.error = Some(ProcessingError {
-                        client: chargeback.client,
-                        tx: chargeback.transaction_id,
-                        kind: ProcessingErrorKind::ChargebackWhenTxNotUnderDispute,
-                    });
+        let clients: Vec<_> = processor.summary().map(|summary| summary.client).collect();
 
-                    return;
-                }
+        assert_eq!(clients.len(), 2);
+        assert!(clients.contains(&ClientId::from(1)));
 
-                if tx_state.is_deposit {
-                    account.held -= tx_state.amount;
-                } else {
-                    account.available += tx_state.amount;
-                    account.held -= tx_state.amount;
-                }
+        let mut processor = TransactionProcessor::default();
 
-                account.locked = true;
-                tx_state.is_under_dispute = false;
-            }
-        }
+        let _ = processor.handle(deposit(3.into(), 4.into(), Decimal::ONE));
+        processor.clear_account(3.into());
 
-        self.add_transaction(tx);
+        assert_eq!(processor.summary().count(), 0);
     }
 
-    pub fn summary(self) -> impl Iterator<Item = AccountSummary> {
-        self.accounts
-            .into_iter()
-            .filter(|(_, client)| client.error.is_none())
-            .map(|(client, account)| {
-                let available = account.available;
-                let held = account.held;
+    #[test]
+    fn test_chargeback_exceeding_held_is_rejected() {
+        let mut processor = TransactionProcessor::default()
+            .with_opening_balances(HashMap::from([(
+                ClientId::from(1),
+                (Decimal::TEN, Decimal::ONE, false),
+            )]))
+            .with_disputes([DisputedTransaction {
+                client: 1.into(),
+                tx: 1.into(),
+                amount: Decimal::new(5, 0),
+                is_deposit: false,
+            }]);
 
-                AccountSummary {
-                    client,
-                    available,
-                    held,
-                    total: available + held,
-                    locked: account.locked,
-                }
-            })
-    }
+        let _ = processor.handle(chargeback(1.into(), 1.into()));
 
-    fn add_transaction(&mut self, tx: Transaction) {
-        let tx_id = tx.tx_id();
+        let account = &processor.accounts[&ClientId::from(1)];
 
-        let state = match tx {
-            Transaction::Deposit(deposit) => TransactionState {
-                amount: deposit.amount,
-                is_under_dispute: false,
+        check_error_kind(account, ProcessingErrorKind::HeldUnderflow);
+        assert_eq!(account.available, Decimal::TEN);
+        assert_eq!(account.held, Decimal::ONE);
+        assert!(!account.locked);
+        assert_eq!(processor.disputes().len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_exceeding_held_is_rejected() {
+        let disputes = || {
+            [DisputedTransaction {
+                client: 1.into(),
+                tx: 1.into(),
+                amount: Decimal::TEN,
                 is_deposit: true,
-            },
-            Transaction::Withdrawal(withdrawal) => TransactionState {
-                amount: withdrawal.amount,
-                is_under_dispute: false,
-                is_deposit: false,
-            },
-            Transaction::Dispute(_) | Transaction::Resolve(_) | Transaction::Chargeback(_) => {
-                return
-            }
+            }]
+        };
+        let balances = || {
+            HashMap::from([(
+                ClientId::from(1),
+                (Decimal::ZERO, Decimal::new(40, 1), false),
+            )])
         };
 
-        self.transactions.insert(tx_id, state);
-    }
-}
+        let mut processor = TransactionProcessor::default()
+            .with_opening_balances(balances())
+            .with_disputes(disputes());
 
-#[cfg(test)]
-mod test {
-    use rust_decimal::Decimal;
+        let _ = processor.handle(resolve(1.into(), 1.into()));
 
-    use crate::{
-        model::{
-            Chargeback, ClientId, Deposit, Dispute, Resolve, Transaction, TransactionId, Withdrawal,
-        },
-        processor::TransactionProcessor,
-    };
+        let account = &processor.accounts[&ClientId::from(1)];
 
-    use super::*;
+        check_error_kind(account, ProcessingErrorKind::HeldUnderflow);
+        assert_eq!(account.held, Decimal::new(40, 1));
+
+        let mut processor = TransactionProcessor::default()
+            .with_opening_balances(balances())
+            .with_disputes(disputes())
+            .with_held_release_guard(true);
+
+        let _ = processor.handle(resolve(1.into(), 1.into()));
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::ResolveExceedsHeld);
+        assert_eq!(account.held, Decimal::new(40, 1));
+        assert_eq!(processor.disputes().len(), 1);
+    }
 
     #[test]
-    fn test_trying_to_revert_withdrawn_funds_locks_account() {
+    fn test_stats() {
         let mut processor = TransactionProcessor::default();
 
         for tx in [
-            deposit(1.into(), 1.into(), Decimal::new(15, 1)),
-            deposit(1.into(), 2.into(), Decimal::new(15, 1)),
-            withdraw(1.into(), 3.into(), Decimal::new(15, 1)),
-            dispute(1.into(), 1.into()),
-            chargeback(1.into(), 1.into()),
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            withdraw(1.into(), 2.into(), Decimal::new(80, 1)),
+            deposit(2.into(), 3.into(), Decimal::new(30, 1)),
+            deposit(2.into(), 4.into(), Decimal::ONE),
+            dispute(2.into(), 3.into()),
+            chargeback(2.into(), 3.into()),
+            deposit(3.into(), 5.into(), Decimal::ONE),
+            dispute(3.into(), 5.into()),
         ] {
-            processor.handle(tx)
+            let _ = processor.handle(tx);
         }
 
-        dbg!(&processor.accounts[&ClientId::from(1)]);
+        assert_eq!(
+            processor.stats(),
+            ProcessingStats {
+                accounts: 3,
+                locked_accounts: 1,
+                total_held: Decimal::ONE,
+                processed: 7,
+                rejected: 1,
+            }
+        );
+    }
 
-        let summary = processor.summary().next().unwrap();
+    #[test]
+    fn test_stats_count_each_transaction_once() {
+        let mut processor = TransactionProcessor::default().with_zero_account_closing(true);
 
-        assert_eq!(summary.client, 1.into());
-        assert!(summary.locked);
-        assert_eq!(summary.total, Decimal::ZERO);
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            transfer(1.into(), 2.into(), 2.into(), Decimal::new(20, 1)),
+            withdraw(2.into(), 3.into(), Decimal::new(20, 1)),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        // the withdrawal closed the recipient's account
+        assert!(!processor.accounts.contains_key(&ClientId::from(2)));
+        assert_eq!(processor.stats().processed, 3);
+
+        assert!(processor.clear_account(1.into()));
+        assert_eq!(processor.stats().processed, 3);
     }
 
     #[test]
-    fn test_chargeback_locks_account() {
+    fn test_chargeback_after_resolve() {
+        let txs = || {
+            [
+                deposit(1.into(), 1.into(), Decimal::ONE),
+                dispute(1.into(), 1.into()),
+                resolve(1.into(), 1.into()),
+                chargeback(1.into(), 1.into()),
+            ]
+        };
+
         let mut processor = TransactionProcessor::default();
 
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        check_error_kind(
+            &processor.accounts[&ClientId::from(1)],
+            ProcessingErrorKind::ChargebackWhenTxNotUnderDispute,
+        );
+
+        let mut processor =
+            TransactionProcessor::default().with_chargeback_after_resolve_reporting(true);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::ChargebackAfterResolve);
+        assert!(!account.locked);
+        assert_eq!(account.available, Decimal::ONE);
+    }
+
+    #[test]
+    fn test_zero_accounts_are_closed() {
+        let mut processor = TransactionProcessor::default().with_zero_account_closing(true);
+
         for tx in [
-            deposit(1.into(), 2.into(), Decimal::new(15, 1)),
-            dispute(1.into(), 2.into()),
-            chargeback(1.into(), 2.into()),
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            withdraw(1.into(), 2.into(), Decimal::new(50, 1)),
+            deposit(2.into(), 3.into(), Decimal::new(30, 1)),
+            dispute(2.into(), 3.into()),
+            deposit(3.into(), 4.into(), Decimal::ONE),
+            dispute(3.into(), 4.into()),
+            chargeback(3.into(), 4.into()),
         ] {
-            processor.handle(tx)
+            let _ = processor.handle(tx);
         }
 
-        assert!(processor.accounts[&ClientId::from(1)].error.is_none());
+        assert!(!processor.accounts.contains_key(&ClientId::from(1)));
+        // zero available but funds held in dispute
+        assert!(processor.accounts.contains_key(&ClientId::from(2)));
+        // zero balance but locked
+        assert!(processor.accounts.contains_key(&ClientId::from(3)));
 
-        let summary = processor.summary().next().unwrap();
+        let _ = processor.handle(deposit(1.into(), 5.into(), Decimal::ONE));
 
-        assert_eq!(summary.client, 1.into());
-        assert!(summary.locked);
-        assert_eq!(summary.total, Decimal::ZERO);
+        assert_eq!(
+            processor.accounts[&ClientId::from(1)].available,
+            Decimal::ONE
+        );
+        assert_eq!(processor.accounts[&ClientId::from(1)].transaction_count, 1);
     }
 
     #[test]
-    fn test_dispute_withdrawal() {
-        let mut processor = TransactionProcessor::default();
+    fn test_sandbox_chargeback_does_not_lock() {
+        let mut processor = TransactionProcessor::default().with_sandbox(true);
 
         for tx in [
-            deposit(1.into(), 2.into(), Decimal::new(155, 1)),
-            withdraw(1.into(), 3.into(), Decimal::new(50, 1)),
-            dispute(1.into(), 3.into()),
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            deposit(1.into(), 2.into(), Decimal::new(20, 1)),
+            dispute(1.into(), 1.into()),
+            chargeback(1.into(), 1.into()),
+            deposit(1.into(), 3.into(), Decimal::ONE),
         ] {
-            processor.handle(tx);
+            let _ = processor.handle(tx);
         }
 
-        assert!(processor.accounts[&ClientId::from(1)].error.is_none());
+        let account = &processor.accounts[&ClientId::from(1)];
 
-        let summary = processor.summary().next().unwrap();
+        assert!(!account.locked);
+        assert_eq!(account.available, Decimal::new(30, 1));
+        assert_eq!(account.held, Decimal::ZERO);
+    }
 
-        assert_eq!(summary.client, 1.into());
-        assert!(!summary.locked);
-        assert_eq!(summary.available, Decimal::new(105, 1));
-        assert_eq!(summary.held, Decimal::new(50, 1));
+    #[test]
+    fn test_estimated_memory_grows_with_transactions() {
+        let mut processor = TransactionProcessor::default();
+        let empty = processor.estimated_memory_bytes();
+
+        for tx in 0..1000u32 {
+            let _ = processor.handle(deposit(1.into(), tx.into(), Decimal::ONE));
+        }
+
+        let estimate = processor.estimated_memory_bytes();
+
+        assert!(estimate > empty);
+        assert!(estimate >= 1000 * std::mem::size_of::<TransactionState>());
+        assert!(estimate < 1024 * 1024);
     }
 
     #[test]
-    fn test_resolve_fails_if_transaction_not_under_dispute() {
+    fn test_duplicate_chargeback() {
+        let txs = || {
+            [
+                deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+                deposit(1.into(), 2.into(), Decimal::new(20, 1)),
+                dispute(1.into(), 1.into()),
+                chargeback(1.into(), 1.into()),
+                chargeback(1.into(), 1.into()),
+            ]
+        };
+
         let mut processor = TransactionProcessor::default();
 
-        processor.handle(deposit(1.into(), 2.into(), Decimal::new(15, 1)));
-        processor.handle(resolve(1.into(), 2.into()));
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        assert!(account.locked);
+        assert!(account.errors.is_empty());
+        assert_eq!(account.available, Decimal::new(20, 1));
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.transaction_count, 4);
+
+        let mut processor = TransactionProcessor::default().with_sandbox(true);
+
+        for tx in txs() {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
 
         check_error_kind(
-            &processor.accounts[&ClientId::from(1)],
-            ProcessingErrorKind::ResolveWhenTxNotUnderDispute,
+            account,
+            ProcessingErrorKind::ChargebackWhenTxNotUnderDispute,
         );
+        assert_eq!(account.available, Decimal::new(20, 1));
+        assert_eq!(account.held, Decimal::ZERO);
     }
 
     #[test]
-    fn test_dispute_fails_if_not_sufficient_funds() {
+    fn test_allowed_types() {
+        let mut processor =
+            TransactionProcessor::default().with_allowed_types([TransactionType::Deposit]);
+
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::new(50, 1)),
+            withdraw(1.into(), 2.into(), Decimal::ONE),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+
+        check_error_kind(account, ProcessingErrorKind::TransactionTypeNotAllowed);
+        assert_eq!(account.available, Decimal::new(50, 1));
+    }
+
+    #[test]
+    fn test_reconcile_with_tolerance() {
         let mut processor = TransactionProcessor::default();
 
         for tx in [
-            deposit(1.into(), 2.into(), Decimal::new(15, 1)),
-            withdraw(1.into(), 3.into(), Decimal::new(5, 1)),
+            deposit(1.into(), 1.into(), Decimal::new(10000, 3)),
+            deposit(2.into(), 2.into(), Decimal::new(20000, 3)),
+            deposit(3.into(), 3.into(), Decimal::new(30000, 3)),
         ] {
-            processor.handle(tx);
+            let _ = processor.handle(tx);
         }
-        processor.handle(dispute(1.into(), 2.into()));
 
-        check_error_kind(
-            &processor.accounts[&ClientId::from(1)],
-            ProcessingErrorKind::NotSufficientFundsForDispute,
+        let ledger = HashMap::from([
+            (ClientId::from(1), Decimal::new(10000, 3)),
+            (ClientId::from(2), Decimal::new(20004, 3)),
+            (ClientId::from(3), Decimal::new(30020, 3)),
+            (ClientId::from(4), Decimal::ONE),
+        ]);
+
+        assert_eq!(
+            processor.reconcile(&ledger, Decimal::new(5, 3)).unwrap(),
+            [
+                Mismatch {
+                    client: 3.into(),
+                    expected: Decimal::new(30020, 3),
+                    actual: Decimal::new(30000, 3),
+                },
+                Mismatch {
+                    client: 4.into(),
+                    expected: Decimal::ONE,
+                    actual: Decimal::ZERO,
+                },
+            ]
         );
     }
 
     #[test]
-    fn test_returns_error_on_transactions_with_negative_amounts() {
+    fn test_reconcile_overflow() {
+        let processor = TransactionProcessor::default().with_opening_balances(HashMap::from([(
+            1.into(),
+            (Decimal::MAX, Decimal::ONE, false),
+        )]));
+
+        assert_eq!(
+            processor.reconcile(&HashMap::new(), Decimal::ZERO),
+            Err(ReconcileOverflow { client: 1.into() })
+        );
+
+        let processor = TransactionProcessor::default().with_opening_balances(HashMap::from([(
+            1.into(),
+            (Decimal::MAX, Decimal::ZERO, false),
+        )]));
+        let ledger = HashMap::from([(1.into(), Decimal::MIN)]);
+
+        assert_eq!(
+            processor.reconcile(&ledger, Decimal::ZERO),
+            Err(ReconcileOverflow { client: 1.into() })
+        );
+    }
+
+    #[test]
+    fn test_had_errors() {
+        let mut processor = TransactionProcessor::default();
+
+        let _ = processor.handle(deposit(1.into(), 1.into(), Decimal::ONE));
+
+        assert!(!processor.had_errors());
+
+        let _ = processor.handle(withdraw(1.into(), 2.into(), Decimal::TWO));
+
+        assert!(processor.had_errors());
+    }
+
+    #[test]
+    fn test_had_errors_for_unknown_client() {
+        let mut processor = TransactionProcessor::default()
+            .with_opening_balances(HashMap::from([(
+                ClientId::from(1),
+                (Decimal::ZERO, Decimal::ZERO, false),
+            )]))
+            .with_strict_clients(true);
+
+        let _ = processor.handle(deposit(1.into(), 1.into(), Decimal::ONE));
+
+        assert!(!processor.had_errors());
+
+        let _ = processor.handle(deposit(2.into(), 2.into(), Decimal::ONE));
+
+        assert!(!processor.accounts.contains_key(&ClientId::from(2)));
+        assert!(processor.had_errors());
+        assert!(TransactionProcessor::restore(processor.snapshot()).had_errors());
+
+        assert!(processor.clear_account(2.into()));
+        assert!(!processor.had_errors());
+    }
+
+    #[test]
+    fn test_account_keeps_every_error() {
         let mut processor = TransactionProcessor::default();
 
         for tx in [
-            deposit(1.into(), 2.into(), Decimal::new(-10, 1)),
-            withdraw(1.into(), 3.into(), Decimal::new(-5, 1)),
+            deposit(1.into(), 1.into(), Decimal::ONE),
+            withdraw(1.into(), 2.into(), Decimal::TWO),
+            deposit(1.into(), 3.into(), Decimal::ONE),
+            withdraw(1.into(), 4.into(), Decimal::new(5, 0)),
         ] {
-            processor.handle(tx);
-
-            check_error_kind(
-                &processor.accounts[&ClientId::from(1)],
-                ProcessingErrorKind::NegativeAmount,
-            );
+            let _ = processor.handle(tx);
         }
+
+        let account = &processor.accounts[&ClientId::from(1)];
+        let failed: Vec<_> = account.errors.iter().map(|error| error.tx).collect();
+
+        assert_eq!(failed, [TransactionId::from(2), TransactionId::from(4)]);
+        assert!(account
+            .errors
+            .iter()
+            .all(|error| error.kind == ProcessingErrorKind::NotSufficientFunds));
+        // errors don't freeze the account, the deposit in between was applied
+        assert_eq!(account.available, Decimal::TWO);
+
+        let summaries: Vec<_> = processor.summary().collect();
+
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].client, ClientId::from(1));
+        assert_eq!(summaries[0].available, Decimal::TWO);
+        assert_eq!(summaries[0].total, Decimal::TWO);
     }
 
     #[test]
-    fn test_returns_error_on_withdrawal_with_with_insufficient_funds() {
+    fn test_overdraft_appears_in_errors() {
         let mut processor = TransactionProcessor::default();
-        let tx = withdraw(1.into(), 2.into(), Decimal::new(20, 1));
 
-        processor.handle(tx);
+        for tx in [
+            deposit(1.into(), 1.into(), Decimal::ONE),
+            withdraw(1.into(), 2.into(), Decimal::TWO),
+            deposit(2.into(), 3.into(), Decimal::ONE),
+        ] {
+            let _ = processor.handle(tx);
+        }
+
+        let errors: Vec<_> = processor.errors().collect();
 
-        check_error_kind(
-            &processor.accounts[&ClientId::from(1)],
-            ProcessingErrorKind::NotSufficientFunds,
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].client, ClientId::from(1));
+        assert_eq!(errors[0].tx, TransactionId::from(2));
+        assert_eq!(errors[0].kind, ProcessingErrorKind::NotSufficientFunds);
+        assert_eq!(
+            errors[0].to_string(),
+            "client=1 tx=2. Error: Not sufficient funds for executing transaction"
         );
     }
 
     #[test]
-    fn test_dispute_fails_when_transaction_already_disputed() {
+    fn test_handle_returns_rejection() {
         let mut processor = TransactionProcessor::default();
+
+        assert!(processor
+            .handle(deposit(1.into(), 1.into(), Decimal::ONE))
+            .is_ok());
+
+        let error = processor
+            .handle(withdraw(1.into(), 2.into(), Decimal::TWO))
+            .unwrap_err();
+
+        assert_eq!(error.client(), ClientId::from(1));
+        assert_eq!(error.tx(), TransactionId::from(2));
+        assert_eq!(error.kind(), &ProcessingErrorKind::NotSufficientFunds);
+
+        // skipped rather than rejected
+        assert!(processor.handle(dispute(1.into(), 9.into())).is_ok());
+        assert_eq!(processor.errors().count(), 1);
+    }
+
+    #[test]
+    fn test_dispute_exceeding_history() {
+        let mut processor =
+            TransactionProcessor::default().with_max_dispute_to_deposits(Decimal::new(5, 1));
+
         for tx in [
-            deposit(1.into(), 2.into(), Decimal::new(20, 1)),
+            deposit(1.into(), 1.into(), Decimal::new(20, 1)),
+            deposit(1.into(), 2.into(), Decimal::ONE),
+            withdraw(1.into(), 3.into(), Decimal::TWO),
             dispute(1.into(), 2.into()),
+            dispute(1.into(), 3.into()),
         ] {
-            processor.handle(tx);
+            let _ = processor.handle(tx);
         }
 
-        processor.handle(dispute(1.into(), 2.into()));
+        let account = &processor.accounts[&ClientId::from(1)];
 
-        check_error_kind(
-            &processor.accounts[&ClientId::from(1)],
-            ProcessingErrorKind::DisputeReferencesAlreadyDisputedTx,
-        );
+        check_error_kind(account, ProcessingErrorKind::DisputeExceedsHistory);
+        assert_eq!(account.total_deposited, Decimal::new(30, 1));
+        assert_eq!(account.held, Decimal::ONE);
+        assert_eq!(processor.disputes().len(), 1);
     }
 
     fn deposit(client: ClientId, tx: TransactionId, amt: Decimal) -> Transaction {
@@ -402,6 +4507,15 @@ mod test {
         })
     }
 
+    fn transfer(from: ClientId, tx: TransactionId, to: ClientId, amt: Decimal) -> Transaction {
+        Transaction::Transfer(Transfer {
+            from,
+            transaction_id: tx,
+            amount: amt,
+            to,
+        })
+    }
+
     fn chargeback(client: ClientId, tx: TransactionId) -> Transaction {
         Transaction::Chargeback(Chargeback {
             client,
@@ -411,7 +4525,7 @@ mod test {
 
     #[track_caller]
     fn check_error_kind(account: &Account, expected_error_kind: ProcessingErrorKind) {
-        let error = account.error.as_ref().map(|e| &e.kind);
+        let error = account.errors.first().map(|e| &e.kind);
 
         assert_eq!(Some(&expected_error_kind), error);
     }
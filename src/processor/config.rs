@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::model::{ClientId, TransactionType};
+
+/// Tunable behaviour of a [`TransactionProcessor`](super::TransactionProcessor).
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessorConfig {
+    /// Report the number of open deposit and withdrawal disputes as separate summary columns.
+    pub report_open_disputes: bool,
+    /// Report the number of deposits and withdrawals applied to each account as separate summary
+    /// columns, after all others.
+    pub report_transaction_counts: bool,
+    /// Add a `memo` column with the [`AccountMemo`](crate::model::AccountMemo) of each account.
+    pub include_memo: bool,
+    /// Add an `error` column with the first error of each account, left empty for accounts without
+    /// an error.
+    pub include_errors: bool,
+    /// Skip every further transaction of an account once one of its transactions was rejected.
+    /// By default only locked accounts are skipped and the recorded error is informational.
+    pub freeze_on_error: bool,
+    /// Log consecutive identical error kinds of a client once, counting the repetitions.
+    pub collapse_repeated_errors: bool,
+    /// Let resolves through on locked accounts, unlocking the account once its last open dispute
+    /// is resolved.
+    pub auto_unlock_on_last_resolve: bool,
+    /// Transaction types accepted, any other is rejected. All types are accepted when `None`.
+    pub allowed_types: Option<HashSet<TransactionType>>,
+    /// Sandbox mode for integration environments: chargebacks reverse funds but never lock the
+    /// account, so processing continues.
+    pub sandbox: bool,
+    /// Remove an account once a transaction leaves it at exactly zero, unless it is locked, has an
+    /// error or open disputes. A later transaction of the client opens a fresh account, with its
+    /// transaction count reset.
+    pub close_zero_accounts: bool,
+    /// Report a chargeback of a transaction that was disputed and then resolved as
+    /// `ChargebackAfterResolve` rather than `ChargebackWhenTxNotUnderDispute`, as it points at a
+    /// specific operational mistake.
+    pub report_chargeback_after_resolve: bool,
+    /// Report resolves releasing more than the account holds as `ResolveExceedsHeld` rather than
+    /// the generic `HeldUnderflow`. Processing alone never leads there, but seeded balances and
+    /// disputes may not add up.
+    pub guard_held_release: bool,
+    /// Accept disputes of withdrawals, as by default. Ledgers where only deposits can be disputed
+    /// turn it off to reject them.
+    pub allow_withdrawal_disputes: bool,
+    /// Reject disputes of withdrawals by clients that were never credited funds, by a deposit or a
+    /// transfer. Funds credited before [`ProcessorConfig::close_zero_accounts`] removed an account
+    /// still count. Accounts carried over through a snapshot or opening balances are exempt, their
+    /// deposits predate processing.
+    pub reject_disputes_before_funds: bool,
+    /// Reject deposits and withdrawals of clients whose account was not opened up front, and
+    /// ignore their other transactions, instead of opening accounts on first use.
+    pub strict_clients: bool,
+    /// Strip trailing zeros from stored amounts and summarized balances, e.g. `1.5000` becomes
+    /// `1.5`. Values are unchanged, only their scale. Not meant to be combined with fixed
+    /// precision output rounding, which pads the scale back.
+    pub normalize_amounts: bool,
+    /// Leave funds held for withdrawal disputes out of the summarized `total`, which then only
+    /// counts assets. They are still reported in `held`.
+    pub exclude_withdrawal_holds_from_total: bool,
+    /// Reject withdrawals and outgoing transfers until at least this many transactions, of any
+    /// client, were handled since the one that opened the account. Accounts carried over through
+    /// a snapshot or opening balances are old enough.
+    pub min_account_age: Option<usize>,
+    /// Limits applied to every client without an entry in `client_limits`.
+    pub limits: Limits,
+    /// Per-client overrides; fields left unset fall back to `limits`.
+    pub client_limits: HashMap<ClientId, Limits>,
+    /// Lets withdrawals draw against `available + held` instead of `available` only. Funds stay
+    /// held for the dispute, so `available` goes negative by the amount drawn from held funds.
+    /// No other option lets a withdrawal touch held funds.
+    pub allow_withdrawal_from_held: bool,
+    /// Number of times a single transaction may be disputed, counting disputes that were later
+    /// resolved; further disputes are rejected. Unlimited when `None`.
+    pub max_disputes_per_tx: Option<u32>,
+    /// Upper bound on the funds held across all accounts; disputes pushing past it are rejected.
+    pub max_total_held: Option<Decimal>,
+    /// Reject resolves of disputes holding no funds, e.g. of a zero-amount deposit. Such a
+    /// resolve changes no balance and otherwise just closes the dispute.
+    pub reject_zero_value_resolves: bool,
+    /// Fraud heuristic: reject disputes of more than this multiple of an account's cumulative
+    /// deposits.
+    pub max_dispute_to_deposits: Option<Decimal>,
+    /// Keep every rejected transaction so it can be handed back for re-submission.
+    pub retain_rejected: bool,
+    /// How deposits exceeding [`Limits::max_total`] are handled.
+    pub ceiling_policy: CeilingPolicy,
+    /// Who bears a charged back withdrawal.
+    pub withdrawal_chargeback: WithdrawalChargeback,
+    /// Number of decimal places the currency allows, amounts with more are handled according to
+    /// `deposit_excess_precision` and `withdrawal_excess_precision`.
+    pub max_scale: Option<u32>,
+    pub deposit_excess_precision: ExcessPrecision,
+    pub withdrawal_excess_precision: ExcessPrecision,
+    /// Number of decimal places an amount may be written with, deposits, withdrawals and transfers
+    /// with more are rejected. Trailing zeros count, `1.2300` has four.
+    pub max_decimal_places: Option<u32>,
+    /// Number of decimal places the funds held for a dispute are rounded to. The rounding
+    /// difference of a disputed deposit stays in `available` and is settled there on chargeback,
+    /// so no funds are created or lost.
+    pub dispute_hold_scale: Option<u32>,
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        Self {
+            report_open_disputes: false,
+            report_transaction_counts: false,
+            include_memo: false,
+            include_errors: false,
+            freeze_on_error: false,
+            collapse_repeated_errors: false,
+            auto_unlock_on_last_resolve: false,
+            allowed_types: None,
+            sandbox: false,
+            close_zero_accounts: false,
+            report_chargeback_after_resolve: false,
+            guard_held_release: false,
+            allow_withdrawal_disputes: true,
+            reject_disputes_before_funds: false,
+            strict_clients: false,
+            normalize_amounts: false,
+            exclude_withdrawal_holds_from_total: false,
+            min_account_age: None,
+            limits: Limits::default(),
+            client_limits: HashMap::new(),
+            allow_withdrawal_from_held: false,
+            max_disputes_per_tx: None,
+            max_total_held: None,
+            reject_zero_value_resolves: false,
+            max_dispute_to_deposits: None,
+            retain_rejected: false,
+            ceiling_policy: CeilingPolicy::default(),
+            withdrawal_chargeback: WithdrawalChargeback::default(),
+            max_scale: None,
+            deposit_excess_precision: ExcessPrecision::default(),
+            withdrawal_excess_precision: ExcessPrecision::default(),
+            max_decimal_places: None,
+            dispute_hold_scale: None,
+        }
+    }
+}
+
+impl ProcessorConfig {
+    /// The funds held for a dispute of a transaction over `amount`.
+    pub fn dispute_hold(&self, amount: Decimal) -> Decimal {
+        match self.dispute_hold_scale {
+            Some(scale) => amount.round_dp(scale),
+            None => amount,
+        }
+    }
+
+    /// The limits in effect for `client`.
+    pub fn limits_for(&self, client: ClientId) -> Limits {
+        match self.client_limits.get(&client) {
+            Some(overrides) => overrides.or(self.limits),
+            None => self.limits,
+        }
+    }
+}
+
+/// Thresholds that can be set globally and overridden per client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Limits {
+    /// Maximum number of transactions applied to a single client before further ones are rejected.
+    /// Freezes and unfreezes are administrative and don't count.
+    pub max_transactions_per_client: Option<usize>,
+    /// Number of dispute/resolve cycles a single transaction may go through before a further
+    /// dispute is reported as abuse.
+    pub max_dispute_cycles: Option<u32>,
+    /// Number of disputes an account may have open at once, deposits and withdrawals combined.
+    pub max_open_disputes_per_account: Option<usize>,
+    /// Ceiling on an account's `total`, enforced on deposits according to
+    /// [`ProcessorConfig::ceiling_policy`].
+    pub max_total: Option<Decimal>,
+}
+
+impl Limits {
+    fn or(self, fallback: Limits) -> Limits {
+        Limits {
+            max_transactions_per_client: self
+                .max_transactions_per_client
+                .or(fallback.max_transactions_per_client),
+            max_dispute_cycles: self.max_dispute_cycles.or(fallback.max_dispute_cycles),
+            max_open_disputes_per_account: self
+                .max_open_disputes_per_account
+                .or(fallback.max_open_disputes_per_account),
+            max_total: self.max_total.or(fallback.max_total),
+        }
+    }
+}
+
+/// What happens to a deposit that would push an account's `total` above [`Limits::max_total`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CeilingPolicy {
+    /// Reject the deposit with [`ProcessingErrorKind::BalanceCeilingExceeded`].
+    ///
+    /// [`ProcessingErrorKind::BalanceCeilingExceeded`]: super::ProcessingErrorKind::BalanceCeilingExceeded
+    #[default]
+    Reject,
+    /// Credit only the part of the deposit that fits under the ceiling.
+    Cap,
+}
+
+/// How a chargeback of a disputed withdrawal settles the held amount.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalChargeback {
+    /// The withdrawal is treated as fraudulent and the withdrawn funds are returned to the
+    /// client's `available` balance.
+    #[default]
+    Refund,
+    /// The client stays liable for the withdrawal; the hold is released without crediting funds.
+    ClientLiable,
+}
+
+/// What happens to an amount with more decimal places than [`ProcessorConfig::max_scale`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExcessPrecision {
+    /// Reject the transaction with [`ProcessingErrorKind::ExcessPrecision`].
+    ///
+    /// [`ProcessingErrorKind::ExcessPrecision`]: super::ProcessingErrorKind::ExcessPrecision
+    #[default]
+    Reject,
+    /// Round the amount to the allowed precision, half to even.
+    Round,
+}
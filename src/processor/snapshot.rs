@@ -0,0 +1,83 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{ClientId, TransactionId};
+
+use super::ProcessingError;
+
+/// Processor state carried over between runs, see [`TransactionProcessor::snapshot`].
+///
+/// Amounts are serialized as strings so a snapshot round-trips without loss.
+///
+/// [`TransactionProcessor::snapshot`]: super::TransactionProcessor::snapshot
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessorSnapshot {
+    pub accounts: Vec<AccountSnapshot>,
+    pub transactions: Vec<TransactionSnapshot>,
+    /// Number of transactions handled so far, which account ages are measured in.
+    #[serde(default)]
+    pub sequence: usize,
+    /// Clients rejected before they had an account, see
+    /// [`TransactionProcessor::had_errors`](super::TransactionProcessor::had_errors).
+    #[serde(default)]
+    pub rejected_without_account: Vec<ClientId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub client: ClientId,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub available: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub held: Decimal,
+    pub locked: bool,
+    pub open_deposit_disputes: usize,
+    pub open_withdrawal_disputes: usize,
+    pub transaction_count: usize,
+    #[serde(default)]
+    pub deposit_count: usize,
+    #[serde(default)]
+    pub withdrawal_count: usize,
+    #[serde(default, with = "rust_decimal::serde::str")]
+    pub total_deposited: Decimal,
+    /// Every rejection of the account's transactions, in order.
+    #[serde(default)]
+    pub errors: Vec<ProcessingError>,
+    /// See [`Account::opened_at`](crate::model::Account::opened_at).
+    #[serde(default)]
+    pub opened_at: Option<usize>,
+}
+
+impl AccountSnapshot {
+    /// Sum of available and held funds.
+    pub fn total(&self) -> Decimal {
+        self.available.saturating_add(self.held)
+    }
+}
+
+/// A stored deposit, withdrawal or transfer. Later transactions may still dispute the first two,
+/// transfers only keep their id from being reused.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionSnapshot {
+    pub client: ClientId,
+    pub tx: TransactionId,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub amount: Decimal,
+    pub is_deposit: bool,
+    pub is_under_dispute: bool,
+    pub resolve_count: u32,
+    /// Missing from snapshots of older versions, see [`TransactionSnapshot::disputes`].
+    #[serde(default)]
+    pub dispute_count: u32,
+    #[serde(default)]
+    pub is_transfer: bool,
+}
+
+impl TransactionSnapshot {
+    /// Number of times the transaction was disputed. Snapshots of older versions didn't record
+    /// it, in which case it is derived from the resolves and an open dispute.
+    pub fn disputes(&self) -> u32 {
+        self.dispute_count
+            .max(self.resolve_count + u32::from(self.is_under_dispute))
+    }
+}
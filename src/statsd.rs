@@ -0,0 +1,74 @@
+//! Reports [`ProcessingStats`] to a statsd daemon over UDP.
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::processor::ProcessingStats;
+
+/// Sends `stats` to the statsd daemon at `addr`, one datagram per metric named `<prefix>.<name>`.
+/// Every metric is a gauge holding the current value, the transaction counts included, so emitting
+/// repeatedly during a run doesn't add them up.
+pub fn emit(addr: impl ToSocketAddrs, prefix: &str, stats: &ProcessingStats) -> io::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect(addr)?;
+
+    for line in metric_lines(prefix, stats) {
+        socket.send(line.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn metric_lines(prefix: &str, stats: &ProcessingStats) -> [String; 5] {
+    [
+        format!("{prefix}.accounts:{}|g", stats.accounts),
+        format!("{prefix}.locked_accounts:{}|g", stats.locked_accounts),
+        format!("{prefix}.total_held:{}|g", stats.total_held),
+        format!("{prefix}.processed:{}|g", stats.processed),
+        format!("{prefix}.rejected:{}|g", stats.rejected),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    #[test]
+    fn test_emit_sends_metric_lines() {
+        let sink = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sink.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let stats = ProcessingStats {
+            accounts: 3,
+            locked_accounts: 1,
+            total_held: Decimal::new(25, 1),
+            processed: 7,
+            rejected: 2,
+        };
+
+        emit(sink.local_addr().unwrap(), "tp", &stats).unwrap();
+
+        let mut buffer = [0; 64];
+        let received: Vec<_> = (0..5)
+            .map(|_| {
+                let len = sink.recv(&mut buffer).unwrap();
+                String::from_utf8(buffer[..len].to_vec()).unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            received,
+            [
+                "tp.accounts:3|g",
+                "tp.locked_accounts:1|g",
+                "tp.total_held:2.5|g",
+                "tp.processed:7|g",
+                "tp.rejected:2|g",
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use tp::input::{self, InputOptions};
+use tp::output::{self, OutputOptions};
+use tp::processor::TransactionProcessor;
+
+const INPUT_SUFFIX: &str = ".input.csv";
+const OUTPUT_SUFFIX: &str = ".output.csv";
+
+/// Replays every `<name>.input.csv` in `tests/data` and compares the summary against the matching
+/// `<name>.output.csv`. Row order is not significant.
+#[test]
+fn test_golden_files() {
+    let inputs = golden_inputs();
+
+    assert!(!inputs.is_empty(), "no golden files found");
+
+    for input_path in inputs {
+        let name = input_path.file_name().unwrap().to_str().unwrap();
+        let output_path = input_path.with_file_name(name.replace(INPUT_SUFFIX, OUTPUT_SUFFIX));
+
+        let mut processor = TransactionProcessor::default();
+
+        for tx in input::transactions(File::open(&input_path).unwrap(), &InputOptions::default()) {
+            let _ = processor.handle(tx.unwrap());
+        }
+
+        let mut actual = Vec::new();
+        output::write_summaries(&mut actual, &OutputOptions::default(), processor.summary())
+            .unwrap();
+
+        let actual = String::from_utf8(actual).unwrap();
+        let expected = fs::read_to_string(&output_path)
+            .unwrap_or_else(|e| panic!("missing golden {}: {e}", output_path.display()));
+
+        assert_eq!(
+            sorted_lines(&actual),
+            sorted_lines(&expected),
+            "golden mismatch for {name}"
+        );
+    }
+}
+
+fn golden_inputs() -> Vec<PathBuf> {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+
+    let mut inputs: Vec<_> = fs::read_dir(data)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(INPUT_SUFFIX))
+        .collect();
+
+    inputs.sort();
+    inputs
+}
+
+fn sorted_lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<_> = text.lines().collect();
+    lines.sort_unstable();
+    lines
+}
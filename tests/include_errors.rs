@@ -0,0 +1,41 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const INPUT: &str = indoc::indoc! {"
+    type, client, tx, amount
+    deposit, 1, 1, 1.0
+    withdrawal, 1, 2, 5.0
+    deposit, 2, 3, 2.0
+"};
+
+/// `--include-errors` adds the reason the overdrawn client was rejected to its row.
+#[test]
+fn test_include_errors() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg("--include-errors")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(INPUT.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    // the error still fails the run
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        indoc::indoc! {"
+            client,available,held,total,locked,error
+            1,1.0,0.0,1.0,false,Not sufficient funds for executing transaction
+            2,2.0,0.0,2.0,false,
+        "}
+    );
+}
@@ -0,0 +1,28 @@
+use rust_decimal::Decimal;
+use tp::process::process_reader;
+
+/// The library processes a CSV without going through the binary.
+#[test]
+fn test_process_reader() {
+    let csv = indoc::indoc! {"
+        type, client, tx, amount
+        deposit, 1, 1, 2.0
+        deposit, 2, 2, 1.5
+        withdrawal, 1, 3, 0.5
+        dispute, 2, 2,
+    "};
+
+    let processor = process_reader(csv.as_bytes()).unwrap();
+    let summaries: Vec<_> = processor.summary().collect();
+
+    assert_eq!(summaries.len(), 2);
+
+    assert_eq!(summaries[0].client, 1.into());
+    assert_eq!(summaries[0].available, Decimal::new(15, 1));
+    assert_eq!(summaries[0].total, Decimal::new(15, 1));
+
+    assert_eq!(summaries[1].client, 2.into());
+    assert_eq!(summaries[1].available, Decimal::ZERO);
+    assert_eq!(summaries[1].held, Decimal::new(15, 1));
+    assert!(!summaries[1].locked);
+}
@@ -0,0 +1,38 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// `--limit` keeps the rows of the lowest client ids.
+#[test]
+fn test_limit_keeps_lowest_clients() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .args(["--limit", "2"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            indoc::indoc! {"
+                type, client, tx, amount
+                deposit, 4, 1, 1.0
+                deposit, 2, 2, 1.0
+                deposit, 5, 3, 1.0
+                deposit, 1, 4, 1.0
+                deposit, 3, 5, 1.0
+            "}
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,1.0,0.0,1.0,false\n2,1.0,0.0,1.0,false\n"
+    );
+}
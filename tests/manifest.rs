@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `--manifest` describes the run, including a hash of the written summary.
+#[test]
+fn test_manifest_describes_run() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/multiple/jan.csv");
+    let id = std::process::id();
+    let summary = std::env::temp_dir().join(format!("tp-manifest-summary-{id}.csv"));
+    let manifest = std::env::temp_dir().join(format!("tp-manifest-{id}.json"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg(&input)
+        .arg("-o")
+        .arg(&summary)
+        .arg("--manifest")
+        .arg(&manifest)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let written = fs::read(&summary).unwrap();
+    let contents: serde_json::Value =
+        serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+
+    fs::remove_file(&summary).unwrap();
+    fs::remove_file(&manifest).unwrap();
+
+    assert_eq!(contents["inputs"], serde_json::json!([input]));
+    assert_eq!(contents["records"], 2);
+    assert_eq!(contents["accounts"], 2);
+    assert_eq!(contents["locked_accounts"], 0);
+    assert_eq!(contents["errors"], 0);
+    assert_eq!(contents["config"]["sandbox"], false);
+    assert_eq!(contents["output_hash"], format!("{:016x}", fnv1a(&written)));
+}
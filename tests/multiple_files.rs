@@ -0,0 +1,50 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Files are processed left to right as one stream, so a dispute may reference a deposit from an
+/// earlier file.
+#[test]
+fn test_multiple_files_are_one_stream() {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/multiple");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg(data.join("jan.csv"))
+        .arg(data.join("feb.csv"))
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines: Vec<_> = stdout.lines().collect();
+    lines.sort_unstable();
+
+    assert_eq!(
+        lines,
+        [
+            "1,0.0,5.0,5.0,false",
+            "2,0.5,0.0,0.5,false",
+            "client,available,held,total,locked",
+        ]
+    );
+}
+
+#[test]
+fn test_missing_file_is_reported() {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/multiple");
+    let missing = data.join("missing.csv");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg(data.join("jan.csv"))
+        .arg(&missing)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains(&format!("Failed opening `{}`", missing.display())));
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// `-o` writes the summary to a file and leaves stdout empty.
+#[test]
+fn test_summary_written_to_output_file() {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/multiple");
+    let summary = std::env::temp_dir().join(format!("tp-summary-{}.csv", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg(data.join("jan.csv"))
+        .arg("-o")
+        .arg(&summary)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout.is_empty());
+
+    let written = fs::read_to_string(&summary).unwrap();
+    fs::remove_file(&summary).unwrap();
+
+    let mut lines: Vec<_> = written.lines().collect();
+    lines.sort_unstable();
+
+    assert_eq!(
+        lines,
+        [
+            "1,5.0,0.0,5.0,false",
+            "2,1.0,0.0,1.0,false",
+            "client,available,held,total,locked",
+        ]
+    );
+}
@@ -0,0 +1,41 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Drives `--repl` with a scripted session and checks the summary printed after each line.
+#[test]
+fn test_repl_prints_affected_account_per_line() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg("--repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            indoc::indoc! {"
+                deposit,1,1,2.0
+                deposit,2,2,1.0
+                dispute,1,1
+                chargeback,1,1
+            "}
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        indoc::indoc! {"
+            1,2.0,0.0,2.0,false
+            2,1.0,0.0,1.0,false
+            1,0.0,2.0,2.0,false
+            1,0.0,0.0,0.0,true
+        "}
+    );
+}
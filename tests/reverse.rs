@@ -0,0 +1,47 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const INPUT: &str = indoc::indoc! {"
+    type, client, tx, amount
+    deposit, 1, 1, 5.0
+    dispute, 1, 1,
+    deposit, 2, 2, 1.0
+"};
+
+fn run(args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(INPUT.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines: Vec<_> = stdout.lines().map(str::to_owned).collect();
+    lines.sort_unstable();
+    lines.join("\n")
+}
+
+/// Reversed, the dispute precedes its deposit and is ignored, so the funds are never held.
+#[test]
+fn test_reverse_changes_dispute_outcome() {
+    assert_eq!(
+        run(&[]),
+        "1,0.0,5.0,5.0,false\n2,1.0,0.0,1.0,false\nclient,available,held,total,locked"
+    );
+    assert_eq!(
+        run(&["--reverse"]),
+        "1,5.0,0.0,5.0,false\n2,1.0,0.0,1.0,false\nclient,available,held,total,locked"
+    );
+}
@@ -0,0 +1,38 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+
+use tp::processor::ProcessorSnapshot;
+
+/// Runs the binary on a delta file on top of a snapshot, as a daily incremental run would, and
+/// compares the snapshot it leaves behind.
+#[test]
+fn test_snapshot_plus_delta() {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/snapshot");
+    let snapshot_out =
+        std::env::temp_dir().join(format!("tp-snapshot-{}.json", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg(data.join("delta.csv"))
+        .arg("--snapshot-in")
+        .arg(data.join("yesterday.json"))
+        .arg("--snapshot-out")
+        .arg(&snapshot_out)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let actual: ProcessorSnapshot =
+        serde_json::from_reader(File::open(&snapshot_out).unwrap()).unwrap();
+    let expected: ProcessorSnapshot =
+        serde_json::from_str(&fs::read_to_string(data.join("today.json")).unwrap()).unwrap();
+
+    fs::remove_file(&snapshot_out).unwrap();
+
+    assert_eq!(actual, expected);
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Without a filename the transactions are read from stdin.
+#[test]
+fn test_reads_stdin_without_filename() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            indoc::indoc! {"
+                type, client, tx, amount
+                deposit, 1, 1, 2.0
+                withdrawal, 1, 2, 0.5
+            "}
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,1.5,0.0,1.5,false\n"
+    );
+}
+
+/// Accounts ending up with an error make the process fail, after the summary was written. They
+/// are summarized like any other.
+#[test]
+fn test_exit_code_on_errors() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            indoc::indoc! {"
+                type, client, tx, amount
+                deposit, 1, 1, 2.0
+                deposit, 2, 2, 1.0
+                withdrawal, 2, 3, 5.0
+            "}
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines: Vec<_> = stdout.lines().collect();
+    lines.sort_unstable();
+
+    assert_eq!(
+        lines,
+        [
+            "1,2.0,0.0,2.0,false",
+            "2,1.0,0.0,1.0,false",
+            "client,available,held,total,locked",
+        ]
+    );
+}
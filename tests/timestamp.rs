@@ -0,0 +1,41 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `--timestamp` appends the same parseable `processed_at` value to every row.
+#[test]
+fn test_timestamp_column() {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/multiple");
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg(data.join("jan.csv"))
+        .arg("--timestamp")
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+
+    assert_eq!(
+        lines.next(),
+        Some("client,available,held,total,locked,processed_at")
+    );
+
+    let timestamps: Vec<u64> = lines
+        .map(|line| line.rsplit(',').next().unwrap().parse().unwrap())
+        .collect();
+
+    assert_eq!(timestamps.len(), 2);
+    assert_eq!(timestamps[0], timestamps[1]);
+    assert!(timestamps[0] >= started);
+}
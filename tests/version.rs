@@ -0,0 +1,32 @@
+use std::path::Path;
+use std::process::Command;
+
+/// `--version-column` appends the package version to every row.
+#[test]
+fn test_version_column() {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/multiple");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tp"))
+        .arg(data.join("jan.csv"))
+        .arg("--version-column")
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+
+    assert_eq!(
+        lines.next(),
+        Some("client,available,held,total,locked,version")
+    );
+
+    let versions: Vec<_> = lines.map(|line| line.rsplit(',').next().unwrap()).collect();
+
+    assert_eq!(versions, [env!("CARGO_PKG_VERSION"); 2]);
+}